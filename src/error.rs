@@ -0,0 +1,123 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by the GPIO, PWM, SPI and I2C interfaces.
+#[derive(Debug)]
+pub enum Error {
+    /// The sysfs attribute at the path does not exist because the GPIO pin
+    /// or PWM channel has not been exported
+    NotExported(PathBuf),
+    /// The file at the path cannot be opened by this process, for freshly
+    /// exported attributes usually because udev has not applied its rules yet
+    PermissionDenied(PathBuf),
+    /// The kernel, driver or controller does not support the named feature
+    UnsupportedFeature(String),
+    /// An argument was rejected, either up front or by the kernel
+    InvalidArgument(String),
+    /// A sysfs attribute held contents that could not be parsed
+    UnexpectedSysfsContent { path: PathBuf, contents: String },
+    /// The line, device or channel is already in use by another consumer
+    Busy(String),
+    /// A character device returned a truncated or unknown event
+    InvalidEvent(String),
+    /// Waiting for an event or for attributes to become available timed out
+    Timeout,
+    /// Any other I/O error
+    Io(io::Error)
+}
+
+impl Error {
+    /// The errno corresponding to this error, if there is one
+    pub fn errno(&self) -> Option<i32> {
+        match *self {
+            Error::NotExported(_) => Some(libc::ENOENT),
+            Error::PermissionDenied(_) => Some(libc::EACCES),
+            Error::UnsupportedFeature(_) => Some(libc::EOPNOTSUPP),
+            Error::InvalidArgument(_) => Some(libc::EINVAL),
+            Error::UnexpectedSysfsContent { .. } => None,
+            Error::Busy(_) => Some(libc::EBUSY),
+            Error::InvalidEvent(_) => None,
+            Error::Timeout => Some(libc::ETIMEDOUT),
+            Error::Io(ref e) => e.raw_os_error()
+        }
+    }
+
+    /// Classify an error from opening the file at `path`
+    pub(crate) fn open(path: &Path, e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_path_buf()),
+            _ => Error::from(e)
+        }
+    }
+
+    /// Classify an error from opening the sysfs attribute at `path`
+    ///
+    /// A missing attribute in a missing directory means the pin or channel
+    /// is not exported.
+    pub(crate) fn attribute(path: &Path, e: io::Error) -> Error {
+        let exported = path.parent().map_or(true, |dir| dir.exists());
+
+        match e.kind() {
+            io::ErrorKind::NotFound if !exported => Error::NotExported(path.to_path_buf()),
+            _ => Error::open(path, e)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::NotExported(ref path) => write!(f, "Not exported: {}", path.display()),
+            Error::PermissionDenied(ref path) => write!(f, "Permission denied: {}", path.display()),
+            Error::UnsupportedFeature(ref feature) => write!(f, "Feature not supported: {}", feature),
+            Error::InvalidArgument(ref msg) => write!(f, "Invalid argument: {}", msg),
+            Error::UnexpectedSysfsContent { ref path, ref contents } => {
+                write!(f, "Unexpected contents {:?} in {}", contents, path.display())
+            }
+            Error::Busy(ref msg) => write!(f, "Busy: {}", msg),
+            Error::InvalidEvent(ref msg) => write!(f, "Invalid event: {}", msg),
+            Error::Timeout => write!(f, "Timed out"),
+            Error::Io(ref e) => e.fmt(f)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        match e.kind() {
+            io::ErrorKind::TimedOut => Error::Timeout,
+            _ => Error::Io(e)
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        let kind = match e {
+            Error::Io(e) => return e,
+            Error::NotExported(_) => io::ErrorKind::NotFound,
+            Error::PermissionDenied(_) => io::ErrorKind::PermissionDenied,
+            Error::UnsupportedFeature(_) => io::ErrorKind::Unsupported,
+            Error::InvalidArgument(_) => io::ErrorKind::InvalidInput,
+            Error::UnexpectedSysfsContent { .. } => io::ErrorKind::InvalidData,
+            Error::Busy(_) => io::ErrorKind::Other,
+            Error::InvalidEvent(_) => io::ErrorKind::InvalidData,
+            Error::Timeout => io::ErrorKind::TimedOut
+        };
+
+        io::Error::new(kind, e)
+    }
+}
@@ -1,3 +1,10 @@
 // A Rust library for peripheral I/O (GPIO, PWM, SPI, I2C, MMIO) in Linux.
 
+mod error;
+pub mod motor;
+pub mod sequencer;
+pub mod servo;
+pub mod soft_pwm;
 pub mod sys;
+
+pub use error::{Error, Result};
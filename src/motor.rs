@@ -0,0 +1,115 @@
+// DC motors on an H-bridge, with a PWM channel for the speed and two GPIO
+// pins selecting the direction.
+
+use crate::sys::gpio::{Direction, Pin, Value};
+use crate::sys::pwm::{Polarity, Pwm, PwmState};
+use crate::{Error, Result};
+
+/// A DC motor behind an H-bridge such as the L298N or TB6612.
+///
+/// The bridge is driven forward with `forward` high and `backward` low and
+/// the other way round in reverse. Both low lets the motor coast, both high
+/// brakes it.
+#[derive(Debug)]
+pub struct Motor {
+    pwm: Pwm,
+    forward: Pin,
+    backward: Pin,
+    period_ns: u64
+}
+
+impl Motor {
+    /// Wrap `pwm` and the direction pins, which have to be exported already
+    ///
+    /// The pins are switched to outputs and the motor starts out coasting.
+    pub fn new(pwm: Pwm, forward: Pin, backward: Pin, period_ns: u64) -> Result<Motor> {
+        if period_ns == 0 {
+            return Err(Error::InvalidArgument("Motor PWM period is 0".to_string()))
+        }
+
+        let motor = Motor {
+            pwm,
+            forward,
+            backward,
+            period_ns
+        };
+
+        motor.forward.set_direction(Direction::Low)?;
+        motor.backward.set_direction(Direction::Low)?;
+        motor.pwm.apply(&motor.state(0, false))?;
+
+        Ok(motor)
+    }
+
+    pub fn pwm(&self) -> &Pwm {
+        &self.pwm
+    }
+
+    /// Drive the motor at `speed` in the range [-1.0, 1.0]
+    ///
+    /// Positive speeds run forward, negative speeds in reverse and 0.0
+    /// coasts. The output is stopped while the direction changes so the
+    /// bridge never sees both inputs switching under load.
+    pub fn set_speed(&self, speed: f64) -> Result<()> {
+        if !(-1.0..=1.0).contains(&speed) {
+            return Err(Error::InvalidArgument(format!("Motor speed {} is outside [-1.0, 1.0]", speed)))
+        }
+
+        if speed == 0.0 {
+            return self.coast()
+        }
+
+        let (forward, backward) = if speed > 0.0 {
+            (Value::High, Value::Low)
+        } else {
+            (Value::Low, Value::High)
+        };
+
+        if self.forward.value()? != forward || self.backward.value()? != backward {
+            self.pwm.apply(&self.state(0, false))?;
+            self.forward.set_value(forward)?;
+            self.backward.set_value(backward)?;
+        }
+
+        let duty_cycle_ns = (speed.abs() * self.period_ns as f64).round() as u64;
+        self.pwm.apply(&self.state(duty_cycle_ns, true))
+    }
+
+    /// The current speed in the range [-1.0, 1.0], 0.0 when coasting or braking
+    pub fn speed(&self) -> Result<f64> {
+        let state = self.pwm.state()?;
+        let forward = self.forward.value()?;
+        let backward = self.backward.value()?;
+
+        if !state.enabled || forward == backward || state.period_ns == 0 {
+            return Ok(0.0)
+        }
+
+        let speed = state.duty_cycle_ns as f64 / state.period_ns as f64;
+        Ok(if forward == Value::High { speed } else { -speed })
+    }
+
+    /// Let the motor spin down freely
+    pub fn coast(&self) -> Result<()> {
+        self.pwm.apply(&self.state(0, false))?;
+        self.forward.set_value(Value::Low)?;
+        self.backward.set_value(Value::Low)
+    }
+
+    /// Short the motor terminals to stop it quickly
+    pub fn brake(&self) -> Result<()> {
+        self.pwm.apply(&self.state(0, false))?;
+        self.forward.set_value(Value::High)?;
+        self.backward.set_value(Value::High)?;
+        self.pwm.apply(&self.state(self.period_ns, true))
+    }
+
+    fn state(&self, duty_cycle_ns: u64, enabled: bool) -> PwmState {
+        PwmState {
+            period_ns: self.period_ns,
+            duty_cycle_ns,
+            polarity: Polarity::Normal,
+            enabled
+        }
+    }
+}
@@ -0,0 +1,365 @@
+// Duty cycle ramps and sequences played on a PWM output from a background
+// thread.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::sys::pwm::PwmOutput;
+use crate::{Error, Result};
+
+/// How a ramp moves between two duty cycles.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Curve {
+    /// Equal duty cycle steps over time
+    Linear,
+    /// Equal steps in perceived brightness, slow at the dark end and fast at
+    /// the bright end, for fading LEDs
+    Exponential
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Step {
+    Set(f64),
+    Hold(Duration),
+    Ramp { to: f64, duration: Duration, curve: Curve }
+}
+
+/// A list of duty cycle changes, built up step by step and played with
+/// `Sequence::start`.
+///
+/// Ramps start from the duty cycle the output is at when they begin. The
+/// output's period has to be configured first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    steps: Vec<Step>,
+    interval: Duration,
+    repeat: bool
+}
+
+impl Default for Sequence {
+    fn default() -> Sequence {
+        Sequence::new()
+    }
+}
+
+impl Sequence {
+    pub fn new() -> Sequence {
+        Sequence {
+            steps: Vec::new(),
+            interval: Duration::from_millis(10),
+            repeat: false
+        }
+    }
+
+    /// Jump to `duty_cycle`
+    pub fn set(mut self, duty_cycle: f64) -> Sequence {
+        self.steps.push(Step::Set(duty_cycle));
+        self
+    }
+
+    /// Keep the current duty cycle for `duration`
+    pub fn hold(mut self, duration: Duration) -> Sequence {
+        self.steps.push(Step::Hold(duration));
+        self
+    }
+
+    /// Move to `to` over `duration`
+    pub fn ramp(mut self, to: f64, duration: Duration, curve: Curve) -> Sequence {
+        self.steps.push(Step::Ramp { to, duration, curve });
+        self
+    }
+
+    /// Play `duty_cycles` one after the other, each for `duration`
+    pub fn duty_cycles<I: IntoIterator<Item = f64>>(mut self, duty_cycles: I, duration: Duration) -> Sequence {
+        for duty_cycle in duty_cycles {
+            self.steps.push(Step::Set(duty_cycle));
+            self.steps.push(Step::Hold(duration));
+        }
+        self
+    }
+
+    /// How often ramps update the duty cycle, 10ms by default
+    pub fn interval(mut self, interval: Duration) -> Sequence {
+        self.interval = interval;
+        self
+    }
+
+    /// Start over after the last step until cancelled
+    pub fn repeat(mut self, repeat: bool) -> Sequence {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Play the sequence on `output` from a new thread
+    pub fn start<P>(self, output: Arc<P>) -> Result<Sequencer>
+    where
+        P: PwmOutput + Send + Sync + ?Sized + 'static
+    {
+        self.validate()?;
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::Running),
+            changed: Condvar::new()
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("pwm-sequencer".to_string())
+                .spawn(move || {
+                    let result = self.play(&*output, &shared);
+                    let mut state = shared.lock();
+                    *state = match result {
+                        Ok(()) if *state == State::Cancelling => State::Cancelled,
+                        Ok(()) => State::Finished,
+                        Err(_) => State::Failed
+                    };
+                    shared.changed.notify_all();
+                    result
+                })?
+        };
+
+        Ok(Sequencer {
+            shared,
+            thread: Some(thread)
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.interval.is_zero() {
+            return Err(Error::InvalidArgument("Sequence interval is 0".to_string()))
+        }
+
+        for step in &self.steps {
+            if let Step::Set(duty_cycle) | Step::Ramp { to: duty_cycle, .. } = *step {
+                if !(0.0..=1.0).contains(&duty_cycle) {
+                    return Err(Error::InvalidArgument(format!("Duty cycle {} is not between 0.0 and 1.0", duty_cycle)))
+                }
+            }
+        }
+
+        let takes_time = self.steps.iter().any(|step| match *step {
+            Step::Hold(duration) | Step::Ramp { duration, .. } => !duration.is_zero(),
+            Step::Set(_) => false
+        });
+
+        if self.repeat && !takes_time {
+            return Err(Error::InvalidArgument("A repeating sequence needs a step that takes time".to_string()))
+        }
+
+        Ok(())
+    }
+
+    fn play<P: PwmOutput + ?Sized>(&self, output: &P, shared: &Shared) -> Result<()> {
+        loop {
+            for step in &self.steps {
+                match *step {
+                    Step::Set(duty_cycle) => output.set_duty_cycle(duty_cycle)?,
+                    Step::Hold(duration) => {
+                        if !shared.sleep_until(Instant::now() + duration) {
+                            return Ok(())
+                        }
+                    }
+                    Step::Ramp { to, duration, curve } => {
+                        let from = output.duty_cycle()?;
+                        let start = Instant::now();
+                        let mut tick = start;
+
+                        loop {
+                            let t = if duration.is_zero() {
+                                1.0
+                            } else {
+                                (start.elapsed().as_secs_f64() / duration.as_secs_f64()).min(1.0)
+                            };
+                            output.set_duty_cycle(curve.at(from, to, t))?;
+
+                            if t >= 1.0 {
+                                break
+                            }
+
+                            tick += self.interval;
+                            if !shared.sleep_until(tick.min(start + duration)) {
+                                return Ok(())
+                            }
+                        }
+                    }
+                }
+
+                if shared.cancelled() {
+                    return Ok(())
+                }
+            }
+
+            if !self.repeat {
+                return Ok(())
+            }
+        }
+    }
+}
+
+impl Curve {
+    /// The duty cycle at `t` in the range [0.0, 1.0] of a ramp from `from` to `to`
+    fn at(self, from: f64, to: f64, t: f64) -> f64 {
+        if t >= 1.0 {
+            return to
+        }
+
+        match self {
+            Curve::Linear => from + (to - from) * t,
+            Curve::Exponential => {
+                // Interpolate in perceived brightness, with duty = (K^level - 1) / (K - 1)
+                const K: f64 = 1024.0;
+                let level = |duty: f64| (duty * (K - 1.0) + 1.0).ln() / K.ln();
+                let level = level(from) + (level(to) - level(from)) * t;
+                ((K.powf(level) - 1.0) / (K - 1.0)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Running,
+    Cancelling,
+    Finished,
+    Cancelled,
+    Failed
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn cancelled(&self) -> bool {
+        *self.lock() == State::Cancelling
+    }
+
+    /// Sleep until `deadline`, returns `false` if cancelled first
+    fn sleep_until(&self, deadline: Instant) -> bool {
+        let mut state = self.lock();
+
+        loop {
+            let now = Instant::now();
+
+            if *state == State::Cancelling {
+                return false
+            }
+            if now >= deadline {
+                return true
+            }
+
+            state = self.changed.wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+}
+
+/// How a sequence ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Finished,
+    Cancelled
+}
+
+/// A sequence playing in the background.
+///
+/// Dropping it cancels the sequence and waits for the thread to stop. The
+/// output is left at the last duty cycle written.
+#[derive(Debug)]
+pub struct Sequencer {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Result<()>>>
+}
+
+impl Sequencer {
+    /// Stop the sequence at the next update
+    pub fn cancel(&self) {
+        let mut state = self.shared.lock();
+
+        if *state == State::Running {
+            *state = State::Cancelling;
+            self.shared.changed.notify_all();
+        }
+    }
+
+    /// `true` once the sequence has ended, for any reason
+    pub fn is_finished(&self) -> bool {
+        !matches!(*self.shared.lock(), State::Running | State::Cancelling)
+    }
+
+    /// Wait up to `timeout` for the sequence to end, returns `is_finished`
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let state = self.shared.lock();
+        let (state, _) = self.shared.changed
+            .wait_timeout_while(state, timeout, |state| matches!(*state, State::Running | State::Cancelling))
+            .unwrap_or_else(|e| e.into_inner());
+
+        !matches!(*state, State::Running | State::Cancelling)
+    }
+
+    /// Wait for the sequence to end
+    ///
+    /// Fails with the error that stopped the sequence, if writing to the
+    /// output failed.
+    pub fn wait(mut self) -> Result<Outcome> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<Outcome> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(Outcome::Cancelled)
+        };
+
+        thread.join().unwrap_or_else(|_| {
+            Err(Error::Io(std::io::Error::other("PWM sequencer thread panicked")))
+        })?;
+
+        match *self.shared.lock() {
+            State::Cancelled => Ok(Outcome::Cancelled),
+            _ => Ok(Outcome::Finished)
+        }
+    }
+}
+
+impl Drop for Sequencer {
+    fn drop(&mut self) {
+        self.cancel();
+        let _ = self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_start_and_end_at_the_ramp_limits() {
+        for curve in [Curve::Linear, Curve::Exponential] {
+            assert!((curve.at(0.25, 0.75, 0.0) - 0.25).abs() < 1e-9);
+            assert_eq!(curve.at(0.25, 0.75, 1.0), 0.75);
+            assert_eq!(curve.at(0.0, 0.3, 1.5), 0.3);
+        }
+    }
+
+    #[test]
+    fn linear_curves_move_in_equal_steps() {
+        assert!((Curve::Linear.at(0.0, 1.0, 0.5) - 0.5).abs() < 1e-9);
+        assert!((Curve::Linear.at(1.0, 0.2, 0.25) - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exponential_curves_start_slowly() {
+        assert!(Curve::Exponential.at(0.0, 1.0, 0.5) < 0.05);
+        assert!(Curve::Exponential.at(1.0, 0.0, 0.5) < 0.05);
+        assert!(Curve::Exponential.at(0.0, 1.0, 0.9) > 0.4);
+    }
+}
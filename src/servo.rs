@@ -0,0 +1,122 @@
+// Hobby servos driven by a hardware PWM channel.
+
+use crate::sys::pwm::{Polarity, Pwm, PwmState};
+use crate::{Error, Result};
+
+/// The pulse range of a servo and the angles it maps to.
+///
+/// The defaults match most hobby servos: a 50 Hz signal with pulses from
+/// 1 ms at 0° to 2 ms at 180°.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ServoConfig {
+    pub period_ns: u64,
+    pub min_pulse_ns: u64,
+    pub max_pulse_ns: u64,
+    pub min_angle: f64,
+    pub max_angle: f64
+}
+
+impl Default for ServoConfig {
+    fn default() -> ServoConfig {
+        ServoConfig {
+            period_ns: 20_000_000,
+            min_pulse_ns: 1_000_000,
+            max_pulse_ns: 2_000_000,
+            min_angle: 0.0,
+            max_angle: 180.0
+        }
+    }
+}
+
+/// A servo on an exported `Pwm` channel.
+///
+/// Angles and pulses outside the configured range are clamped to it, so the
+/// servo is never driven past its limits.
+#[derive(Debug)]
+pub struct Servo {
+    pwm: Pwm,
+    config: ServoConfig
+}
+
+impl Servo {
+    /// Wrap `pwm`, which has to be exported already
+    ///
+    /// The output is left untouched until the first position is set.
+    pub fn new(pwm: Pwm, config: ServoConfig) -> Result<Servo> {
+        if config.min_pulse_ns >= config.max_pulse_ns || config.max_pulse_ns > config.period_ns {
+            return Err(Error::InvalidArgument(format!(
+                "Servo pulse range {}ns..{}ns does not fit the period {}ns",
+                config.min_pulse_ns, config.max_pulse_ns, config.period_ns
+            )))
+        }
+
+        if !config.min_angle.is_finite() || !config.max_angle.is_finite() || config.min_angle == config.max_angle {
+            return Err(Error::InvalidArgument(format!(
+                "Servo angle range {}..{} is empty",
+                config.min_angle, config.max_angle
+            )))
+        }
+
+        Ok(Servo { pwm, config })
+    }
+
+    pub fn config(&self) -> &ServoConfig {
+        &self.config
+    }
+
+    pub fn pwm(&self) -> &Pwm {
+        &self.pwm
+    }
+
+    pub fn into_inner(self) -> Pwm {
+        self.pwm
+    }
+
+    /// Move to `angle`, clamped to the configured angle range
+    pub fn set_angle(&self, angle: f64) -> Result<()> {
+        if angle.is_nan() {
+            return Err(Error::InvalidArgument("Servo angle is NaN".to_string()))
+        }
+
+        let c = &self.config;
+        let (low, high) = if c.min_angle < c.max_angle {
+            (c.min_angle, c.max_angle)
+        } else {
+            (c.max_angle, c.min_angle)
+        };
+        let ratio = (angle.max(low).min(high) - c.min_angle) / (c.max_angle - c.min_angle);
+        let range = (c.max_pulse_ns - c.min_pulse_ns) as f64;
+
+        self.set_pulse_ns(c.min_pulse_ns + (ratio * range).round() as u64)
+    }
+
+    /// The angle of the current pulse
+    pub fn angle(&self) -> Result<f64> {
+        let c = &self.config;
+        let pulse_ns = self.pulse_ns()?.max(c.min_pulse_ns).min(c.max_pulse_ns);
+        let ratio = (pulse_ns - c.min_pulse_ns) as f64 / (c.max_pulse_ns - c.min_pulse_ns) as f64;
+
+        Ok(c.min_angle + ratio * (c.max_angle - c.min_angle))
+    }
+
+    /// Output pulses of `pulse_ns`, clamped to the configured pulse range
+    pub fn set_pulse_ns(&self, pulse_ns: u64) -> Result<()> {
+        let c = &self.config;
+
+        self.pwm.apply(&PwmState {
+            period_ns: c.period_ns,
+            duty_cycle_ns: pulse_ns.max(c.min_pulse_ns).min(c.max_pulse_ns),
+            polarity: Polarity::Normal,
+            enabled: true
+        })
+    }
+
+    pub fn pulse_ns(&self) -> Result<u64> {
+        self.pwm.duty_cycle_ns()
+    }
+
+    /// Stop sending pulses, most servos then stop holding their position
+    pub fn disable(&self) -> Result<()> {
+        self.pwm.enable(false)
+    }
+}
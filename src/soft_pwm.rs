@@ -0,0 +1,333 @@
+// Software PWM on a sysfs GPIO pin, for boards short on PWM channels.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::sys::gpio::{Direction, Pin};
+use crate::sys::pwm::{self, PwmOutput};
+use crate::{Error, Result};
+
+/// A PWM signal generated by toggling a `Pin` from a dedicated thread.
+///
+/// The timing is only as accurate as the scheduler allows, expect jitter in
+/// the tens of microseconds. That is fine for LEDs and most motors, but not
+/// for servos. Dropping it stops the thread and drives the pin low.
+#[derive(Debug)]
+pub struct SoftPwm {
+    pin: Pin,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>
+}
+
+#[derive(Debug)]
+struct Shared {
+    config: Mutex<Config>,
+    changed: Condvar
+}
+
+#[derive(Debug)]
+struct Config {
+    period_ns: u64,
+    duty_cycle_ns: u64,
+    enabled: bool,
+    /// Bumped on every change so the thread restarts its period
+    generation: u64,
+    stop: bool,
+    /// The write error that stopped the thread
+    error: Option<io::Error>
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Config> {
+        self.config.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl SoftPwm {
+    /// Start a disabled software PWM on `pin`, which has to be exported already
+    ///
+    /// The pin is switched to an output driven low.
+    pub fn new(pin: Pin, frequency_hz: f64) -> Result<SoftPwm> {
+        let period_ns = pwm::frequency_period_ns(frequency_hz)?;
+
+        pin.set_direction(Direction::Low)?;
+
+        let value = crate::sys::open_attribute(&pin.sys_file_path("value"))?;
+
+        let shared = Arc::new(Shared {
+            config: Mutex::new(Config {
+                period_ns,
+                duty_cycle_ns: 0,
+                enabled: false,
+                generation: 0,
+                stop: false,
+                error: None
+            }),
+            changed: Condvar::new()
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name(format!("soft-pwm-gpio{}", pin.num))
+                .spawn(move || run(&shared, &value))?
+        };
+
+        Ok(SoftPwm {
+            pin,
+            shared,
+            thread: Some(thread)
+        })
+    }
+
+    pub fn pin(&self) -> Pin {
+        self.pin
+    }
+
+    pub fn enable(&self, enable: bool) -> Result<()> {
+        self.update(|config| {
+            config.enabled = enable;
+            Ok(())
+        })
+    }
+
+    pub fn enabled(&self) -> Result<bool> {
+        self.read(|config| config.enabled)
+    }
+
+    pub fn period_ns(&self) -> Result<u64> {
+        self.read(|config| config.period_ns)
+    }
+
+    /// Set the period, which may not be shorter than the duty cycle
+    pub fn set_period_ns(&self, period_ns: u64) -> Result<()> {
+        self.update(|config| {
+            validate(config.duty_cycle_ns, period_ns)?;
+            config.period_ns = period_ns;
+            Ok(())
+        })
+    }
+
+    pub fn duty_cycle_ns(&self) -> Result<u64> {
+        self.read(|config| config.duty_cycle_ns)
+    }
+
+    /// Set the duty cycle, which may not be longer than the period
+    pub fn set_duty_cycle_ns(&self, duty_cycle_ns: u64) -> Result<()> {
+        self.update(|config| {
+            validate(duty_cycle_ns, config.period_ns)?;
+            config.duty_cycle_ns = duty_cycle_ns;
+            Ok(())
+        })
+    }
+
+    /// Get the frequency in Hz, 0.0 if no period is configured
+    pub fn frequency_hz(&self) -> Result<f64> {
+        let period_ns = self.period_ns()?;
+
+        if period_ns == 0 {
+            return Ok(0.0)
+        }
+
+        Ok(1e9 / period_ns as f64)
+    }
+
+    /// Set the frequency in Hz, keeping the duty ratio
+    pub fn set_frequency_hz(&self, frequency_hz: f64) -> Result<()> {
+        let period_ns = pwm::frequency_period_ns(frequency_hz)?;
+
+        self.update(|config| {
+            let ratio = pwm::duty_ratio(config.duty_cycle_ns, config.period_ns);
+            config.period_ns = period_ns;
+            config.duty_cycle_ns = (ratio * period_ns as f64).round() as u64;
+            Ok(())
+        })
+    }
+
+    /// Get the duty cycle as a ratio of the period, from 0.0 to 1.0
+    pub fn duty_cycle(&self) -> Result<f64> {
+        self.read(|config| pwm::duty_ratio(config.duty_cycle_ns, config.period_ns))
+    }
+
+    /// Set the duty cycle as a ratio of the period, from 0.0 to 1.0
+    pub fn set_duty_cycle(&self, duty_cycle: f64) -> Result<()> {
+        self.update(|config| {
+            config.duty_cycle_ns = pwm::duty_cycle_ns(duty_cycle, config.period_ns)?;
+            Ok(())
+        })
+    }
+
+    fn read<T, F: FnOnce(&Config) -> T>(&self, f: F) -> Result<T> {
+        let config = self.shared.lock();
+        check(&config)?;
+        Ok(f(&config))
+    }
+
+    fn update<F: FnOnce(&mut Config) -> Result<()>>(&self, f: F) -> Result<()> {
+        let mut config = self.shared.lock();
+        check(&config)?;
+        f(&mut config)?;
+        config.generation += 1;
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+impl PwmOutput for SoftPwm {
+    fn enable(&self, enable: bool) -> Result<()> {
+        SoftPwm::enable(self, enable)
+    }
+
+    fn enabled(&self) -> Result<bool> {
+        SoftPwm::enabled(self)
+    }
+
+    fn period_ns(&self) -> Result<u64> {
+        SoftPwm::period_ns(self)
+    }
+
+    fn set_period_ns(&self, period_ns: u64) -> Result<()> {
+        SoftPwm::set_period_ns(self, period_ns)
+    }
+
+    fn duty_cycle_ns(&self) -> Result<u64> {
+        SoftPwm::duty_cycle_ns(self)
+    }
+
+    fn set_duty_cycle_ns(&self, duty_cycle_ns: u64) -> Result<()> {
+        SoftPwm::set_duty_cycle_ns(self, duty_cycle_ns)
+    }
+
+    fn frequency_hz(&self) -> Result<f64> {
+        SoftPwm::frequency_hz(self)
+    }
+
+    fn set_frequency_hz(&self, frequency_hz: f64) -> Result<()> {
+        SoftPwm::set_frequency_hz(self, frequency_hz)
+    }
+
+    fn duty_cycle(&self) -> Result<f64> {
+        SoftPwm::duty_cycle(self)
+    }
+
+    fn set_duty_cycle(&self, duty_cycle: f64) -> Result<()> {
+        SoftPwm::set_duty_cycle(self, duty_cycle)
+    }
+}
+
+impl Drop for SoftPwm {
+    fn drop(&mut self) {
+        self.shared.lock().stop = true;
+        self.shared.changed.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let _ = self.pin.set_direction(Direction::Low);
+    }
+}
+
+fn validate(duty_cycle_ns: u64, period_ns: u64) -> Result<()> {
+    if duty_cycle_ns > period_ns {
+        return Err(Error::InvalidArgument(format!(
+            "Duty cycle {}ns is longer than the period {}ns",
+            duty_cycle_ns, period_ns
+        )))
+    }
+
+    Ok(())
+}
+
+/// Fail if the thread stopped on a write error
+fn check(config: &Config) -> Result<()> {
+    match config.error {
+        Some(ref e) => Err(Error::Io(match e.raw_os_error() {
+            Some(errno) => io::Error::from_raw_os_error(errno),
+            None => io::Error::new(e.kind(), e.to_string())
+        })),
+        None => Ok(())
+    }
+}
+
+fn run(shared: &Shared, value: &File) {
+    let mut config = shared.lock();
+    let mut level = None;
+
+    while !config.stop {
+        let generation = config.generation;
+        let high = Duration::from_nanos(config.duty_cycle_ns);
+        let period = Duration::from_nanos(config.period_ns);
+
+        if !config.enabled || high.is_zero() || high == period {
+            let result = set_level(value, &mut level, config.enabled && !high.is_zero());
+            if let Err(e) = result {
+                config.error = Some(e);
+                return
+            }
+
+            config = wait_until(shared, config, generation, None);
+            continue
+        }
+
+        let start = Instant::now();
+        if let Err(e) = set_level(value, &mut level, true) {
+            config.error = Some(e);
+            return
+        }
+
+        config = wait_until(shared, config, generation, Some(start + high));
+        if config.stop || config.generation != generation {
+            continue
+        }
+
+        if let Err(e) = set_level(value, &mut level, false) {
+            config.error = Some(e);
+            return
+        }
+
+        config = wait_until(shared, config, generation, Some(start + period));
+    }
+}
+
+/// Drive the pin, skipping the write if it is already at that level
+fn set_level(value: &File, level: &mut Option<bool>, high: bool) -> io::Result<()> {
+    if *level != Some(high) {
+        // The file stays open between writes, so always write at the start
+        value.write_at(if high { b"1" } else { b"0" }, 0)?;
+        *level = Some(high);
+    }
+
+    Ok(())
+}
+
+/// Sleep until `deadline`, waking early when the configuration changes
+fn wait_until<'a>(
+    shared: &'a Shared,
+    mut config: MutexGuard<'a, Config>,
+    generation: u64,
+    deadline: Option<Instant>
+) -> MutexGuard<'a, Config> {
+    loop {
+        if config.stop || config.generation != generation {
+            return config
+        }
+
+        config = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return config
+                }
+
+                shared.changed.wait_timeout(config, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner()).0
+            }
+            None => shared.changed.wait(config).unwrap_or_else(|e| e.into_inner())
+        };
+    }
+}
@@ -1,8 +1,13 @@
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::path::Path;
-use std::io::{self, Write, Read};
-use std::io::ErrorKind::{InvalidData, Other};
+use std::path::{Path, PathBuf};
+use std::io::{self, Write, Read, Seek, SeekFrom};
+use std::io::ErrorKind::Interrupted;
+use std::ops::Deref;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+use crate::{Error, Result};
 
 #[derive(Debug, Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
 pub enum Direction {
@@ -27,6 +32,18 @@ pub enum Edge {
     Both // 上升、下降都沿
 }
 
+/// An edge detected on an input.
+#[derive(Debug, Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub struct EdgeEvent {
+    /// `Edge::Rising` or `Edge::Falling`
+    pub edge: Edge,
+    /// `CLOCK_MONOTONIC` time at which the waiting thread woke up
+    ///
+    /// sysfs does not timestamp interrupts, so this trails the actual edge
+    /// by the scheduling latency.
+    pub timestamp: Duration
+}
+
 #[derive(Debug, Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
 pub struct Pin {
     pub num: usize
@@ -37,69 +54,102 @@ impl Pin {
         Pin { num }
     }
 
-    pub fn from_path<T: AsRef<Path>>(path: T) -> io::Result<Pin> {
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<Pin> {
         let pb = fs::canonicalize(path.as_ref())?;
 
         if !fs::metadata(&pb)?.is_dir() {
-            return Err(io::Error::new(
-                Other,
-                "Provided path not a directory or symlink to a directory".to_owned()
+            return Err(Error::InvalidArgument(
+                format!("{} is not a directory or symlink to a directory", path.as_ref().display())
             ));
         }
         let num = Pin::extract_pin_from_path(&pb)?;
         Ok(Pin::new(num))
     }
 
-    fn extract_pin_from_path<P: AsRef<Path>>(path: P) -> io::Result<usize> {
+    fn extract_pin_from_path<P: AsRef<Path>>(path: P) -> Result<usize> {
         path.as_ref()
             .file_name()
             .and_then(|filename| filename.to_str())
             .and_then(|filename_str| filename_str.trim_start_matches("gpio").parse::<usize>().ok())
-            .ok_or(io::Error::new(InvalidData, format!("{:?}", path.as_ref())))
+            .ok_or_else(|| Error::InvalidArgument(format!("{} is not a gpio directory", path.as_ref().display())))
     }
 
-    fn write_sys_file(&self, file_name: &str, value: &str) -> io::Result<()> {
-        let path = format!("/sys/class/gpio/gpio{}/{}", self.num, file_name);
+    pub(crate) fn sys_file_path(&self, file_name: &str) -> PathBuf {
+        super::path(format!("sys/class/gpio/gpio{}/{}", self.num, file_name))
+    }
 
-        let mut file = OpenOptions::new().write(true).open(&path)?;
+    fn write_sys_file(&self, file_name: &str, value: &str) -> Result<()> {
+        let mut file = super::open_attribute(&self.sys_file_path(file_name))?;
         file.write_all(value.as_bytes())?;
-        
+
         Ok(())
     }
 
-    fn read_sys_file(&self, file_name: &str) -> io::Result<String> {
-        let path = format!("/sys/class/gpio/gpio{}/{}", self.num, file_name);
+    fn read_sys_file(&self, file_name: &str) -> Result<String> {
+        let path = self.sys_file_path(file_name);
 
-        let mut file = File::open(&path)?;
+        let mut file = File::open(&path).map_err(|e| Error::attribute(&path, e))?;
         let mut s = String::new();
         file.read_to_string(&mut s)?;
 
         Ok(s)
     }
 
+    fn unexpected_content(&self, file_name: &str, contents: &str) -> Error {
+        Error::UnexpectedSysfsContent {
+            path: self.sys_file_path(file_name),
+            contents: contents.to_owned()
+        }
+    }
+
     pub fn is_exported(&self) -> bool {
-        fs::metadata(&format!("/sys/class/gpio/gpio{}", self.num)).is_ok()
+        fs::metadata(super::path(format!("sys/class/gpio/gpio{}", self.num))).is_ok()
     }
 
-    pub fn export(&self) -> io::Result<()> {
+    pub fn export(&self) -> Result<()> {
         if !self.is_exported() {
-            let mut file = OpenOptions::new().write(true).open("/sys/class/gpio/export")?;
+            let path = super::path("sys/class/gpio/export");
+            let mut file = OpenOptions::new().write(true).open(&path)
+                .map_err(|e| Error::open(&path, e))?;
             file.write_all(format!("{}", self.num).as_bytes())?;
         }
 
         Ok(())
     }
 
-    pub fn unexport(&self) -> io::Result<()> {
+    /// Export the pin and wait up to `timeout` for its attributes to become writable
+    ///
+    /// Right after export the attributes belong to root until udev applies
+    /// its rules, so non-root users should use this instead of `export`.
+    pub fn export_wait(&self, timeout: Duration) -> Result<()> {
+        self.export()?;
+        self.wait_writable(timeout)
+    }
+
+    fn wait_writable(&self, timeout: Duration) -> Result<()> {
+        let dir = super::path(format!("sys/class/gpio/gpio{}", self.num));
+        let mut attributes = vec![dir.join("value")];
+
+        // Pins with a fixed direction have no direction attribute
+        if fs::metadata(dir.join("direction")).is_ok() {
+            attributes.push(dir.join("direction"));
+        }
+
+        super::wait_writable(&attributes, timeout)
+    }
+
+    pub fn unexport(&self) -> Result<()> {
         if self.is_exported() {
-            let mut file = OpenOptions::new().write(true).open("/sys/class/gpio/unexport")?;
+            let path = super::path("sys/class/gpio/unexport");
+            let mut file = OpenOptions::new().write(true).open(&path)
+                .map_err(|e| Error::open(&path, e))?;
             file.write_all(format!("{}", self.num).as_bytes())?;
         }
 
         Ok(())
     }
 
-    pub fn direction(&self) -> io::Result<Direction> {
+    pub fn direction(&self) -> Result<Direction> {
         match self.read_sys_file("direction") {
             Ok(s) => {
                 match s.trim() {
@@ -107,17 +157,14 @@ impl Pin {
                     "out" => Ok(Direction::Out),
                     "high" => Ok(Direction::High),
                     "low" => Ok(Direction::Low),
-                    other => Err(io::Error::new(
-                        Other,
-                        format!("direction file contents {}", other)
-                    ))
+                    other => Err(self.unexpected_content("direction", other))
                 }
             }
-            Err(e) => Err(::std::convert::From::from(e)),
+            Err(e) => Err(e),
         }
     }
 
-    pub fn set_direction(&self, dir: Direction) -> io::Result<()> {
+    pub fn set_direction(&self, dir: Direction) -> Result<()> {
         self.write_sys_file("direction", match dir {
                 Direction::In => "in",
                 Direction::Out => "out",
@@ -128,23 +175,20 @@ impl Pin {
         Ok(())
     }
 
-    pub fn value(&self) -> io::Result<Value> {
+    pub fn value(&self) -> Result<Value> {
         match self.read_sys_file("value") {
             Ok(s) => {
                 match s.trim() {
                     "1" => Ok(Value::High),
                     "0" => Ok(Value::Low),
-                    other => Err(io::Error::new(
-                        Other,
-                        format!("value file contents {}", other)
-                    ))
+                    other => Err(self.unexpected_content("value", other))
                 }
             }
             Err(e) => Err(e),
         }
     }
 
-    pub fn set_value(&self, value: Value) -> io::Result<()> {
+    pub fn set_value(&self, value: Value) -> Result<()> {
         self.write_sys_file("value", match value {
                 Value::Low => "0",
                 Value::High => "1"
@@ -153,7 +197,7 @@ impl Pin {
         Ok(())
     }
 
-    pub fn edge(&self) -> io::Result<Edge> {
+    pub fn edge(&self) -> Result<Edge> {
         match self.read_sys_file("edge") {
             Ok(s) => {
                 match s.trim() {
@@ -161,17 +205,14 @@ impl Pin {
                     "rising" => Ok(Edge::Rising),
                     "falling" => Ok(Edge::Falling),
                     "both" => Ok(Edge::Both),
-                    other => Err(io::Error::new(
-                        Other,
-                        format!("Unexpected file contents {}", other)
-                    ))
+                    other => Err(self.unexpected_content("edge", other))
                 }
             }
             Err(e) => Err(e),
         }
     }
 
-    pub fn set_edge(&self, edge: Edge) -> io::Result<()> {
+    pub fn set_edge(&self, edge: Edge) -> Result<()> {
         self.write_sys_file("edge", match edge {
                 Edge::None => "none",
                 Edge::Rising => "rising",
@@ -182,23 +223,20 @@ impl Pin {
         Ok(())
     }
 
-    pub fn active_low(&self) -> io::Result<bool> {
+    pub fn active_low(&self) -> Result<bool> {
         match self.read_sys_file("active_low") {
             Ok(s) => {
                 match s.trim() {
                     "1" => Ok(true),
                     "0" => Ok(false),
-                    other => Err(io::Error::new(
-                        Other,
-                        format!("active_low file contents {}", other)
-                    ))
+                    other => Err(self.unexpected_content("active_low", other))
                 }
             }
             Err(e) => Err(e),
         }
     }
 
-    pub fn set_active_low(&self, active_low: bool) -> io::Result<()> {
+    pub fn set_active_low(&self, active_low: bool) -> Result<()> {
         self.write_sys_file("active_low",
                 match active_low {
                     true => "1",
@@ -207,4 +245,210 @@ impl Pin {
 
         Ok(())
     }
+
+    /// Wait up to `timeout` for the edge configured with `set_edge`
+    ///
+    /// Returns `Ok(None)` if the timeout expired, `None` waits forever.
+    /// Edges occurring between calls are not seen, use `events` to
+    /// keep watching the pin.
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<Option<EdgeEvent>> {
+        self.events()?.poll(timeout)
+    }
+
+    /// Block until the edge configured with `set_edge` occurs
+    pub fn wait_for_edge(&self) -> Result<EdgeEvent> {
+        self.events()?.wait()
+    }
+
+    /// Watch the pin for edges, see `PinEvents`
+    ///
+    /// The edge configured with `set_edge` is read once here, so set it first.
+    /// With `Edge::None` the pin would never wake up, `Error::InvalidArgument`
+    /// is returned instead.
+    pub fn events(&self) -> Result<PinEvents> {
+        let edge = self.edge()?;
+        if edge == Edge::None {
+            return Err(Error::InvalidArgument(format!("gpio{} has no edge configured", self.num)))
+        }
+
+        let path = self.sys_file_path("value");
+
+        let mut file = File::open(&path).map_err(|e| Error::attribute(&path, e))?;
+
+        // Reading the value clears any pending notification, so only edges
+        // after this point are reported.
+        let mut s = String::new();
+        file.read_to_string(&mut s)?;
+
+        Ok(PinEvents { pin: *self, edge, file })
+    }
+}
+
+/// A `Pin` that stays exported for the lifetime of the guard.
+///
+/// On creation the pin is exported and the guard waits for udev to make its
+/// attributes writable. On drop the pin is switched back to an input, unless
+/// disabled with `set_disable_on_drop`, and unexported, so a panicking
+/// process does not leave it driving its output. A pin that was already
+/// exported when the guard was created is left untouched.
+#[derive(Debug)]
+pub struct ExportedPin {
+    pin: Pin,
+    disable_on_drop: bool,
+    /// Whether this guard exported the pin
+    unexport_on_drop: bool
+}
+
+impl ExportedPin {
+    /// Export `pin`, waiting up to one second for its attributes to become writable
+    pub fn new(pin: Pin) -> Result<ExportedPin> {
+        ExportedPin::with_timeout(pin, super::DEFAULT_EXPORT_TIMEOUT)
+    }
+
+    /// Export `pin`, waiting up to `timeout` for its attributes to become writable
+    pub fn with_timeout(pin: Pin, timeout: Duration) -> Result<ExportedPin> {
+        let unexport_on_drop = !pin.is_exported();
+        pin.export()?;
+
+        // Created before waiting so the pin is unexported again on failure
+        let guard = ExportedPin {
+            pin,
+            disable_on_drop: unexport_on_drop,
+            unexport_on_drop
+        };
+
+        pin.wait_writable(timeout)?;
+
+        Ok(guard)
+    }
+
+    pub fn pin(&self) -> Pin {
+        self.pin
+    }
+
+    /// Whether to switch the pin to an input on drop, by default only if the
+    /// guard exported it
+    pub fn set_disable_on_drop(&mut self, disable_on_drop: bool) {
+        self.disable_on_drop = disable_on_drop;
+    }
+}
+
+impl Deref for ExportedPin {
+    type Target = Pin;
+
+    fn deref(&self) -> &Pin {
+        &self.pin
+    }
+}
+
+impl Drop for ExportedPin {
+    fn drop(&mut self) {
+        if self.disable_on_drop {
+            let _ = self.pin.set_direction(Direction::In);
+        }
+
+        if self.unexport_on_drop {
+            let _ = self.pin.unexport();
+        }
+    }
+}
+
+/// A stream of edge events on a sysfs `Pin`.
+///
+/// Holds the `value` file open so no edge is missed between waits. Iterating
+/// blocks until the next edge.
+#[derive(Debug)]
+pub struct PinEvents {
+    pin: Pin,
+    /// The configured edge, when only one of them is reported
+    edge: Edge,
+    file: File
+}
+
+impl PinEvents {
+    pub fn pin(&self) -> Pin {
+        self.pin
+    }
+
+    /// Wait up to `timeout` for the next edge, `None` waits forever
+    pub fn poll(&mut self, timeout: Option<Duration>) -> Result<Option<EdgeEvent>> {
+        if !poll_fd(self.file.as_raw_fd(), libc::POLLPRI | libc::POLLERR, timeout)? {
+            return Ok(None)
+        }
+
+        let timestamp = monotonic_now()?;
+
+        // Reading the value acknowledges the notification
+        let mut s = String::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_string(&mut s)?;
+
+        // sysfs only signals that an edge occurred. With both edges enabled
+        // it is derived from the value read back, which may have changed
+        // again since.
+        let edge = match (self.edge, s.trim()) {
+            (Edge::Rising, _) => Edge::Rising,
+            (Edge::Falling, _) => Edge::Falling,
+            (_, "1") => Edge::Rising,
+            (_, "0") => Edge::Falling,
+            (_, other) => return Err(self.pin.unexpected_content("value", other))
+        };
+
+        Ok(Some(EdgeEvent { edge, timestamp }))
+    }
+
+    /// Block until the next edge
+    pub fn wait(&mut self) -> Result<EdgeEvent> {
+        loop {
+            if let Some(event) = self.poll(None)? {
+                return Ok(event)
+            }
+        }
+    }
+}
+
+impl Iterator for PinEvents {
+    type Item = Result<EdgeEvent>;
+
+    fn next(&mut self) -> Option<Result<EdgeEvent>> {
+        Some(self.wait())
+    }
+}
+
+impl AsRawFd for PinEvents {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Wait for `events` on `fd`, returns `false` if `timeout` expired first
+pub(crate) fn poll_fd(fd: RawFd, events: libc::c_short, timeout: Option<Duration>) -> io::Result<bool> {
+    let timeout_ms = match timeout {
+        Some(timeout) => timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+        None => -1
+    };
+
+    let mut pfd = libc::pollfd {
+        fd,
+        events,
+        revents: 0
+    };
+
+    loop {
+        match syscall!(poll(&mut pfd, 1, timeout_ms)) {
+            Ok(0) => return Ok(false),
+            Ok(_) => return Ok(true),
+            Err(ref e) if e.kind() == Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+    }
+}
+
+/// The current `CLOCK_MONOTONIC` time
+pub(crate) fn monotonic_now() -> io::Result<Duration> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+
+    syscall!(clock_gettime(libc::CLOCK_MONOTONIC, &mut ts))?;
+
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
 }
@@ -0,0 +1,105 @@
+// Async GPIO edge event streams, enabled by the `async-tokio` feature.
+//
+// Both sysfs `Pin`s and gpiochip `Line`s are supported. The underlying fds
+// are registered with the tokio reactor, users of other mio based executors
+// can register `PinEvents` or `Line` directly through their `AsRawFd` impl.
+
+use std::pin::Pin as StdPin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::{ready, Stream};
+use tokio::io::unix::AsyncFd;
+
+use crate::Result;
+use super::gpio::{Pin, Edge, EdgeEvent, PinEvents};
+use super::gpiochip::Line;
+
+/// A `Stream` of edge events on a sysfs `Pin`.
+#[derive(Debug)]
+pub struct AsyncPinEvents {
+    inner: AsyncFd<PinEvents>
+}
+
+impl AsyncPinEvents {
+    /// Configure `pin` to detect `edge` and watch it
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(pin: Pin, edge: Edge) -> Result<AsyncPinEvents> {
+        pin.set_edge(edge)?;
+
+        Ok(AsyncPinEvents {
+            inner: AsyncFd::new(pin.events()?)?
+        })
+    }
+
+    pub fn pin(&self) -> Pin {
+        self.inner.get_ref().pin()
+    }
+}
+
+impl Stream for AsyncPinEvents {
+    type Item = Result<EdgeEvent>;
+
+    fn poll_next(self: StdPin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let mut guard = ready!(this.inner.poll_read_ready_mut(cx))?;
+
+            // sysfs attributes always report readable, the reactor only tells
+            // us the file was woken up. Check for an actual edge without
+            // blocking and go back to waiting if there is none.
+            match guard.get_inner_mut().poll(Some(Duration::from_millis(0))) {
+                Ok(Some(event)) => return Poll::Ready(Some(Ok(event))),
+                Ok(None) => guard.clear_ready(),
+                Err(e) => return Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+/// A `Stream` of edge events on a gpiochip `Line`.
+///
+/// Edge detection has to be enabled through `LineConfig::edge` when the
+/// line is requested.
+#[derive(Debug)]
+pub struct AsyncLineEvents {
+    inner: AsyncFd<Line>
+}
+
+impl AsyncLineEvents {
+    /// Must be called from within a tokio runtime.
+    pub fn new(line: Line) -> Result<AsyncLineEvents> {
+        Ok(AsyncLineEvents {
+            inner: AsyncFd::new(line)?
+        })
+    }
+
+    pub fn line(&self) -> &Line {
+        self.inner.get_ref()
+    }
+
+    /// Stop watching the line and return it
+    pub fn into_inner(self) -> Line {
+        self.inner.into_inner()
+    }
+}
+
+impl Stream for AsyncLineEvents {
+    type Item = Result<EdgeEvent>;
+
+    fn poll_next(self: StdPin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let mut guard = ready!(this.inner.poll_read_ready_mut(cx))?;
+
+            match guard.get_inner_mut().poll(Some(Duration::from_millis(0))) {
+                Ok(Some(event)) => return Poll::Ready(Some(Ok(event))),
+                Ok(None) => guard.clear_ready(),
+                Err(e) => return Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
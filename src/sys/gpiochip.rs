@@ -0,0 +1,1302 @@
+// GPIO character device (`/dev/gpiochipN`) backend, using the GPIO v2 uAPI.
+//
+// This is the replacement for the deprecated `/sys/class/gpio` interface
+// driven by `sys::gpio::Pin`. It shares the `Direction`, `Value` and `Edge`
+// types with the sysfs backend so code can move between the two.
+
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Error, Result};
+use super::gpio::{self, Direction, Value, Edge, EdgeEvent, Pin};
+
+/// A GPIO chip, opened through its character device.
+pub struct GpioChip {
+    path: PathBuf,
+    file: File
+}
+
+/// Information about a GPIO chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChipInfo {
+    /// The kernel name of the chip, e.g. `gpiochip0`
+    pub name: String,
+    /// The functional name of the chip, e.g. the driver or device tree label
+    pub label: String,
+    /// The number of lines the chip provides
+    pub lines: u32
+}
+
+/// A GPIO chip found by `GpioChip::list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioChipInfo {
+    /// The character device name, e.g. `gpiochip0`, if the chip has one
+    pub name: Option<String>,
+    pub label: String,
+    /// The sysfs number of the first line, if the chip is in `/sys/class/gpio`
+    pub base: Option<u32>,
+    pub ngpio: u32
+}
+
+/// The state of a line as reported by the kernel, whether or not it is
+/// requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineInfo {
+    pub offset: u32,
+    /// The name given to the line by the device tree or driver, may be empty
+    pub name: String,
+    /// The name the current user requested the line with, may be empty
+    pub consumer: String,
+    /// Whether the line is in use, by a process or by a kernel driver
+    pub used: bool,
+    /// Either `Direction::In` or `Direction::Out`
+    pub direction: Direction,
+    pub active_low: bool,
+    pub edge: Edge,
+    /// `Bias::AsIs` when the kernel does not know the bias
+    pub bias: Bias,
+    pub drive: Drive,
+    pub debounce: Option<Duration>
+}
+
+/// What happened to a watched line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineChange {
+    Requested,
+    Released,
+    Reconfigured
+}
+
+/// A change to a line watched with `GpioChip::watch_line_info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineInfoChange {
+    pub change: LineChange,
+    /// The line info after the change
+    pub info: LineInfo,
+    /// `CLOCK_MONOTONIC` time of the change
+    pub timestamp: Duration
+}
+
+/// A line located by `GpioChip::find_line` or `GpioChip::find_line_by_name`.
+///
+/// It can be used through either backend, as a sysfs `Pin` or requested
+/// from the character device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioLine {
+    pub chip: GpioChipInfo,
+    pub offset: u32
+}
+
+/// Internal pull resistor configuration of a line.
+#[derive(Debug, Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub enum Bias {
+    /// Leave the bias as configured by firmware or the device tree
+    AsIs,
+    PullUp,
+    PullDown,
+    Disabled
+}
+
+/// Output driver configuration of a line.
+#[derive(Debug, Copy, PartialEq, Eq, Clone, PartialOrd, Ord)]
+pub enum Drive {
+    PushPull,
+    /// Only drives low, the line floats when high
+    OpenDrain,
+    /// Only drives high, the line floats when low
+    OpenSource
+}
+
+/// Configuration applied to a line when it is requested or reconfigured.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineConfig {
+    /// `Direction::Low`/`Direction::High` request an output with that
+    /// initial value, `Direction::Out` leaves the initial value to the kernel.
+    pub direction: Direction,
+    pub active_low: bool,
+    /// Edge detection, only valid for inputs.
+    pub edge: Edge,
+    pub bias: Bias,
+    /// Anything but `Drive::PushPull` is only valid for outputs.
+    pub drive: Drive,
+    /// Debounce period, only valid for inputs. Chips without hardware
+    /// debounce are debounced in software by the kernel.
+    pub debounce: Option<Duration>
+}
+
+impl Default for LineConfig {
+    fn default() -> LineConfig {
+        LineConfig {
+            direction: Direction::In,
+            active_low: false,
+            edge: Edge::None,
+            bias: Bias::AsIs,
+            drive: Drive::PushPull,
+            debounce: None
+        }
+    }
+}
+
+/// A single requested GPIO line.
+///
+/// The line is released when this value is dropped.
+#[derive(Debug)]
+pub struct Line {
+    file: File,
+    offset: u32
+}
+
+impl GpioChip {
+    /// Open `/dev/gpiochip{number}`
+    pub fn new(number: u32) -> Result<GpioChip> {
+        GpioChip::from_path(super::path(format!("dev/gpiochip{}", number)))
+    }
+
+    /// Open a GPIO chip by the path of its character device
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Result<GpioChip> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())
+            .map_err(|e| Error::open(path.as_ref(), e))?;
+
+        let chip = GpioChip {
+            path: path.as_ref().to_path_buf(),
+            file
+        };
+
+        // Make sure this is actually a gpiochip before handing it out
+        chip.info()?;
+
+        Ok(chip)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn info(&self) -> Result<ChipInfo> {
+        let mut info: private::gpiochip_info = unsafe { std::mem::zeroed() };
+
+        private::get_chipinfo(self.file.as_raw_fd(), &mut info)?;
+
+        Ok(ChipInfo {
+            name: private::c_str(&info.name),
+            label: private::c_str(&info.label),
+            lines: info.lines
+        })
+    }
+
+    /// All GPIO chips, from both `/dev` and `/sys/class/gpio`
+    ///
+    /// Chips only visible in one of them are listed with the fields the
+    /// other would provide left empty. Chips that cannot be opened or
+    /// queried are skipped, only failing to read the directories is an error.
+    pub fn list() -> Result<Vec<GpioChipInfo>> {
+        let mut chips = Vec::new();
+
+        for (base, dir) in numbered_entries(&super::path("sys/class/gpio"), "gpiochip")? {
+            // The sysfs chip is a child of the device behind the character device
+            let name = fs::canonicalize(dir.join("device")).ok()
+                .and_then(|device| device.file_name().map(|name| name.to_string_lossy().into_owned()))
+                .filter(|name| name.starts_with("gpiochip"));
+
+            let (label, ngpio) = match (sysfs_attribute(&dir.join("label")), sysfs_number(&dir.join("ngpio"))) {
+                (Ok(label), Ok(ngpio)) => (label.trim_end_matches('\n').to_owned(), ngpio),
+                _ => continue
+            };
+
+            chips.push(GpioChipInfo {
+                name,
+                label,
+                base: Some(base),
+                ngpio
+            });
+        }
+
+        for (_, path) in numbered_entries(&super::path("dev"), "gpiochip")? {
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+
+            if chips.iter().any(|chip| chip.name == name) {
+                continue
+            }
+
+            let info = match GpioChip::from_path(&path).and_then(|chip| chip.info()) {
+                Ok(info) => info,
+                Err(_) => continue
+            };
+            let sysfs = chips.iter_mut()
+                .find(|chip| chip.name.is_none() && chip.label == info.label && chip.ngpio == info.lines);
+
+            match sysfs {
+                Some(chip) => chip.name = Some(info.name),
+                None => chips.push(GpioChipInfo {
+                    name: Some(info.name),
+                    label: info.label,
+                    base: None,
+                    ngpio: info.lines
+                })
+            }
+        }
+
+        chips.sort_by_key(|chip| (chip.base.is_none(), chip.base, chip.name.clone()));
+
+        Ok(chips)
+    }
+
+    /// Locate line `offset` of the chip with the label or character device
+    /// name `chip`
+    pub fn find_line(chip: &str, offset: u32) -> Result<Option<GpioLine>> {
+        let chip = GpioChip::list()?.into_iter()
+            .find(|info| info.label == chip || info.name.as_deref() == Some(chip));
+
+        Ok(chip.filter(|chip| offset < chip.ngpio).map(|chip| GpioLine { chip, offset }))
+    }
+
+    /// Locate a line by the name given to it in the device tree or by the driver
+    ///
+    /// Line names are only available through the character device, chips
+    /// without one, or whose lines cannot be queried, are not searched.
+    pub fn find_line_by_name(name: &str) -> Result<Option<GpioLine>> {
+        for chip in GpioChip::list()? {
+            let device = match chip.name {
+                Some(ref device) => match GpioChip::from_path(super::path(format!("dev/{}", device))) {
+                    Ok(device) => device,
+                    Err(_) => continue
+                },
+                None => continue
+            };
+
+            for offset in 0..chip.ngpio {
+                match device.line_info(offset) {
+                    Ok(ref info) if info.name == name => return Ok(Some(GpioLine { chip, offset })),
+                    Ok(_) => {}
+                    Err(_) => break
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Query the state of a line, including who is using it
+    pub fn line_info(&self, offset: u32) -> Result<LineInfo> {
+        let mut info: private::gpio_v2_line_info = unsafe { std::mem::zeroed() };
+        info.offset = offset;
+
+        private::get_lineinfo(self.file.as_raw_fd(), &mut info).map_err(|e| self.line_info_error(offset, e))?;
+
+        Ok(private::line_info(&info))
+    }
+
+    /// Query the state of a line and report its future changes
+    ///
+    /// Changes are read with `poll_line_info_change` and friends. A line can
+    /// only be watched once per open chip.
+    pub fn watch_line_info(&self, offset: u32) -> Result<LineInfo> {
+        let mut info: private::gpio_v2_line_info = unsafe { std::mem::zeroed() };
+        info.offset = offset;
+
+        private::get_lineinfo_watch(self.file.as_raw_fd(), &mut info).map_err(|e| self.line_info_error(offset, e))?;
+
+        Ok(private::line_info(&info))
+    }
+
+    /// Stop reporting changes of a line watched with `watch_line_info`
+    pub fn unwatch_line_info(&self, offset: u32) -> Result<()> {
+        let mut raw_offset = offset;
+
+        private::get_lineinfo_unwatch(self.file.as_raw_fd(), &mut raw_offset).map_err(|e| match e.raw_os_error() {
+            Some(libc::EBUSY) => Error::InvalidArgument(format!("Line {} is not watched", offset)),
+            _ => self.line_info_error(offset, e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for a change of a watched line, `None` waits forever
+    pub fn poll_line_info_change(&self, timeout: Option<Duration>) -> Result<Option<LineInfoChange>> {
+        if !gpio::poll_fd(self.file.as_raw_fd(), libc::POLLIN, timeout)? {
+            return Ok(None)
+        }
+
+        self.read_line_info_change().map(Some)
+    }
+
+    /// Block until the next change of a watched line
+    pub fn wait_for_line_info_change(&self) -> Result<LineInfoChange> {
+        self.read_line_info_change()
+    }
+
+    /// Iterate over changes of watched lines as they occur
+    pub fn line_info_changes(&self) -> LineInfoChanges<'_> {
+        LineInfoChanges { chip: self }
+    }
+
+    /// Classify kernel errors from line info queries, where EINVAL means the
+    /// chip does not have the line
+    fn line_info_error(&self, offset: u32, e: io::Error) -> Error {
+        match e.raw_os_error() {
+            Some(libc::EINVAL) => Error::InvalidArgument(
+                format!("{} has no line {}", self.path.display(), offset)
+            ),
+            Some(libc::EBUSY) => Error::Busy(
+                format!("Line {} of {} is already watched", offset, self.path.display())
+            ),
+            _ => private::request_error(e)
+        }
+    }
+
+    fn read_line_info_change(&self) -> Result<LineInfoChange> {
+        let mut event: private::gpio_v2_line_info_changed = unsafe { std::mem::zeroed() };
+
+        let buf = unsafe {
+            std::slice::from_raw_parts_mut(
+                &mut event as *mut _ as *mut u8,
+                std::mem::size_of::<private::gpio_v2_line_info_changed>()
+            )
+        };
+
+        private::read_event(&self.file, buf)?;
+
+        let change = match event.event_type {
+            private::GPIO_V2_LINE_CHANGED_REQUESTED => LineChange::Requested,
+            private::GPIO_V2_LINE_CHANGED_RELEASED => LineChange::Released,
+            private::GPIO_V2_LINE_CHANGED_CONFIG => LineChange::Reconfigured,
+            other => return Err(Error::InvalidEvent(format!("Unknown line info change type {}", other)))
+        };
+
+        Ok(LineInfoChange {
+            change,
+            info: private::line_info(&event.info),
+            timestamp: Duration::from_nanos(event.timestamp_ns)
+        })
+    }
+
+    /// Request a single line for exclusive use
+    ///
+    /// `consumer` is the name reported to other users of the chip, it is
+    /// truncated to 31 bytes.
+    pub fn request_line(&self, offset: u32, consumer: &str, config: &LineConfig) -> Result<Line> {
+        let mut request: private::gpio_v2_line_request = unsafe { std::mem::zeroed() };
+
+        request.offsets[0] = offset;
+        request.num_lines = 1;
+        private::copy_c_str(&mut request.consumer, consumer);
+        request.config = private::line_config(&[*config])?;
+
+        private::get_line(self.file.as_raw_fd(), &mut request).map_err(private::request_error)?;
+
+        Ok(Line {
+            file: unsafe { File::from_raw_fd(request.fd) },
+            offset
+        })
+    }
+
+    /// Request several lines sharing the same configuration, see `request_lines_with`
+    pub fn request_lines(&self, offsets: &[u32], consumer: &str, config: &LineConfig) -> Result<Lines> {
+        let lines: Vec<(u32, LineConfig)> = offsets.iter()
+            .map(|offset| (*offset, *config))
+            .collect();
+
+        self.request_lines_with(&lines, consumer)
+    }
+
+    /// Request up to 64 lines at once, each with its own configuration
+    ///
+    /// The lines can then be read and written together in a single call. At
+    /// most 10 distinct configurations can be combined in one request.
+    pub fn request_lines_with(&self, lines: &[(u32, LineConfig)], consumer: &str) -> Result<Lines> {
+        let configs: Vec<LineConfig> = lines.iter().map(|(_, config)| *config).collect();
+
+        let mut request: private::gpio_v2_line_request = unsafe { std::mem::zeroed() };
+
+        request.config = private::line_config(&configs)?;
+        for (dst, (offset, _)) in request.offsets.iter_mut().zip(lines) {
+            *dst = *offset;
+        }
+        request.num_lines = lines.len() as u32;
+        private::copy_c_str(&mut request.consumer, consumer);
+
+        private::get_line(self.file.as_raw_fd(), &mut request).map_err(private::request_error)?;
+
+        Ok(Lines {
+            file: unsafe { File::from_raw_fd(request.fd) },
+            offsets: lines.iter().map(|(offset, _)| *offset).collect()
+        })
+    }
+}
+
+impl AsRawFd for GpioChip {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for GpioChip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GpioChip")
+            .field("path", &self.path)
+            .field("info", &self.info())
+            .finish()
+    }
+}
+
+impl GpioLine {
+    /// The sysfs `Pin` of this line, if its chip has a sysfs base
+    pub fn pin(&self) -> Option<Pin> {
+        self.chip.base.map(|base| Pin::new((base + self.offset) as usize))
+    }
+
+    /// Open the character device of the line's chip
+    pub fn open_chip(&self) -> Result<GpioChip> {
+        match self.chip.name {
+            Some(ref name) => GpioChip::from_path(super::path(format!("dev/{}", name))),
+            None => Err(Error::UnsupportedFeature(format!("GPIO character device for {}", self.chip.label)))
+        }
+    }
+
+    /// Request the line through the character device, see `GpioChip::request_line`
+    pub fn request(&self, consumer: &str, config: &LineConfig) -> Result<Line> {
+        self.open_chip()?.request_line(self.offset, consumer, config)
+    }
+}
+
+impl Line {
+    /// The offset of the line within its chip
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn value(&self) -> Result<Value> {
+        let mut values = private::gpio_v2_line_values {
+            bits: 0,
+            mask: 0x01
+        };
+
+        private::get_values(self.file.as_raw_fd(), &mut values)?;
+
+        Ok(if values.bits & 0x01 == 0 { Value::Low } else { Value::High })
+    }
+
+    pub fn set_value(&self, value: Value) -> Result<()> {
+        let mut values = private::gpio_v2_line_values {
+            bits: value as u64,
+            mask: 0x01
+        };
+
+        private::set_values(self.file.as_raw_fd(), &mut values)?;
+
+        Ok(())
+    }
+
+    /// Change the configuration of the line without releasing it
+    pub fn reconfigure(&self, config: &LineConfig) -> Result<()> {
+        let mut line_config = private::line_config(&[*config])?;
+
+        private::set_config(self.file.as_raw_fd(), &mut line_config).map_err(private::request_error)?;
+
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for an edge, `None` waits forever
+    ///
+    /// Edge detection has to be enabled through `LineConfig::edge`. Events
+    /// are queued by the kernel, so none are lost between calls.
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<Option<EdgeEvent>> {
+        if !gpio::poll_fd(self.file.as_raw_fd(), libc::POLLIN, timeout)? {
+            return Ok(None)
+        }
+
+        self.read_event().map(Some)
+    }
+
+    /// Block until the next edge
+    pub fn wait_for_edge(&self) -> Result<EdgeEvent> {
+        self.read_event()
+    }
+
+    /// Iterate over edges as they occur
+    pub fn events(&self) -> LineEvents<'_> {
+        LineEvents { line: self }
+    }
+
+    fn read_event(&self) -> Result<EdgeEvent> {
+        let mut event: private::gpio_v2_line_event = Default::default();
+
+        let buf = unsafe {
+            std::slice::from_raw_parts_mut(
+                &mut event as *mut _ as *mut u8,
+                std::mem::size_of::<private::gpio_v2_line_event>()
+            )
+        };
+
+        private::read_event(&self.file, buf)?;
+
+        let edge = match event.id {
+            private::GPIO_V2_LINE_EVENT_RISING_EDGE => Edge::Rising,
+            private::GPIO_V2_LINE_EVENT_FALLING_EDGE => Edge::Falling,
+            other => return Err(Error::InvalidEvent(format!("Unknown line event id {}", other)))
+        };
+
+        Ok(EdgeEvent {
+            edge,
+            timestamp: Duration::from_nanos(event.timestamp_ns)
+        })
+    }
+}
+
+/// A group of GPIO lines requested together.
+///
+/// Values are exchanged as bitmasks where bit `i` is the `i`th line of the
+/// request, so all lines change in a single kernel call. The lines are
+/// released when this value is dropped.
+#[derive(Debug)]
+pub struct Lines {
+    file: File,
+    offsets: Vec<u32>
+}
+
+impl Lines {
+    /// The offsets of the lines, in request order
+    pub fn offsets(&self) -> &[u32] {
+        &self.offsets
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    fn mask(&self) -> u64 {
+        if self.offsets.len() >= 64 {
+            !0
+        } else {
+            (1 << self.offsets.len()) - 1
+        }
+    }
+
+    /// Read the values of all lines
+    pub fn values(&self) -> Result<u64> {
+        let mut values = private::gpio_v2_line_values {
+            bits: 0,
+            mask: self.mask()
+        };
+
+        private::get_values(self.file.as_raw_fd(), &mut values)?;
+
+        Ok(values.bits)
+    }
+
+    /// Set the values of all lines
+    pub fn set_values(&self, bits: u64) -> Result<()> {
+        self.set_values_masked(bits, self.mask())
+    }
+
+    /// Set the values of the lines selected by `mask`, leaving the others untouched
+    pub fn set_values_masked(&self, bits: u64, mask: u64) -> Result<()> {
+        if mask & !self.mask() != 0 {
+            return Err(Error::InvalidArgument(
+                format!("Mask {:#x} selects lines outside of the request", mask)
+            ))
+        }
+
+        let mut values = private::gpio_v2_line_values {
+            bits: bits & mask,
+            mask
+        };
+
+        private::set_values(self.file.as_raw_fd(), &mut values)?;
+
+        Ok(())
+    }
+
+    /// Change the configuration of the lines, one `LineConfig` per line in request order
+    pub fn reconfigure(&self, configs: &[LineConfig]) -> Result<()> {
+        if configs.len() != self.offsets.len() {
+            return Err(Error::InvalidArgument(
+                format!("Expected {} line configurations, got {}", self.offsets.len(), configs.len())
+            ))
+        }
+
+        let mut line_config = private::line_config(configs)?;
+
+        private::set_config(self.file.as_raw_fd(), &mut line_config).map_err(private::request_error)?;
+
+        Ok(())
+    }
+}
+
+impl AsRawFd for Lines {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Blocking iterator over the changes of lines watched on a `GpioChip`.
+#[derive(Debug)]
+pub struct LineInfoChanges<'a> {
+    chip: &'a GpioChip
+}
+
+impl<'a> Iterator for LineInfoChanges<'a> {
+    type Item = Result<LineInfoChange>;
+
+    fn next(&mut self) -> Option<Result<LineInfoChange>> {
+        Some(self.chip.read_line_info_change())
+    }
+}
+
+/// Blocking iterator over the edge events of a `Line`.
+#[derive(Debug)]
+pub struct LineEvents<'a> {
+    line: &'a Line
+}
+
+impl<'a> Iterator for LineEvents<'a> {
+    type Item = Result<EdgeEvent>;
+
+    fn next(&mut self) -> Option<Result<EdgeEvent>> {
+        Some(self.line.read_event())
+    }
+}
+
+impl AsRawFd for Line {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// The entries of `dir` named `{prefix}{number}`, or nothing if `dir` is missing
+fn numbered_entries(dir: &Path, prefix: &str) -> Result<Vec<(u32, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::open(dir, e))
+    };
+
+    let mut numbered = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let Some(number) = entry.file_name().to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|number| number.parse::<u32>().ok())
+        {
+            numbered.push((number, entry.path()));
+        }
+    }
+    numbered.sort_unstable();
+
+    Ok(numbered)
+}
+
+fn sysfs_attribute(path: &Path) -> Result<String> {
+    let mut buf = Vec::new();
+    File::open(path)
+        .map_err(|e| Error::open(path, e))?
+        .read_to_end(&mut buf)?;
+
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn sysfs_number(path: &Path) -> Result<u32> {
+    let s = sysfs_attribute(path)?;
+
+    s.trim().parse::<u32>().map_err(|_| Error::UnexpectedSysfsContent {
+        path: path.to_path_buf(),
+        contents: s
+    })
+}
+
+mod private {
+    #![allow(dead_code)]
+
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::raw::c_char;
+    use std::time::Duration;
+
+    use crate::{Error, Result};
+    use crate::sys::gpio::{Direction, Edge};
+    use super::{LineConfig, LineInfo, Bias, Drive};
+
+    pub const GPIO_MAX_NAME_SIZE: usize = 32;
+    pub const GPIO_V2_LINES_MAX: usize = 64;
+    pub const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+
+    pub const GPIO_V2_LINE_FLAG_USED: u64 = 1 << 0;
+    pub const GPIO_V2_LINE_FLAG_ACTIVE_LOW: u64 = 1 << 1;
+    pub const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+    pub const GPIO_V2_LINE_FLAG_OUTPUT: u64 = 1 << 3;
+    pub const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+    pub const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+    pub const GPIO_V2_LINE_FLAG_OPEN_DRAIN: u64 = 1 << 6;
+    pub const GPIO_V2_LINE_FLAG_OPEN_SOURCE: u64 = 1 << 7;
+    pub const GPIO_V2_LINE_FLAG_BIAS_PULL_UP: u64 = 1 << 8;
+    pub const GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN: u64 = 1 << 9;
+    pub const GPIO_V2_LINE_FLAG_BIAS_DISABLED: u64 = 1 << 10;
+    pub const GPIO_V2_LINE_FLAG_EVENT_CLOCK_REALTIME: u64 = 1 << 11;
+
+    pub const GPIO_V2_LINE_ATTR_ID_FLAGS: u32 = 1;
+    pub const GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES: u32 = 2;
+    pub const GPIO_V2_LINE_ATTR_ID_DEBOUNCE: u32 = 3;
+
+    pub const GPIO_V2_LINE_EVENT_RISING_EDGE: u32 = 1;
+    pub const GPIO_V2_LINE_EVENT_FALLING_EDGE: u32 = 2;
+
+    pub const GPIO_V2_LINE_CHANGED_REQUESTED: u32 = 1;
+    pub const GPIO_V2_LINE_CHANGED_RELEASED: u32 = 2;
+    pub const GPIO_V2_LINE_CHANGED_CONFIG: u32 = 3;
+
+    const GPIO_IOC_MAGIC: u8 = 0xB4;
+    const GPIO_IOC_NR_GET_CHIPINFO: u8 = 0x01;
+    const GPIO_IOC_NR_GET_LINEINFO_UNWATCH: u8 = 0x0C;
+    const GPIO_V2_IOC_NR_GET_LINEINFO: u8 = 0x05;
+    const GPIO_V2_IOC_NR_GET_LINEINFO_WATCH: u8 = 0x06;
+    const GPIO_V2_IOC_NR_GET_LINE: u8 = 0x07;
+    const GPIO_V2_IOC_NR_LINE_SET_CONFIG: u8 = 0x0D;
+    const GPIO_V2_IOC_NR_LINE_GET_VALUES: u8 = 0x0E;
+    const GPIO_V2_IOC_NR_LINE_SET_VALUES: u8 = 0x0F;
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct gpiochip_info {
+        pub name: [c_char; GPIO_MAX_NAME_SIZE],
+        pub label: [c_char; GPIO_MAX_NAME_SIZE],
+        pub lines: u32
+    }
+
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Default, Copy, Clone)]
+    #[repr(C)]
+    pub struct gpio_v2_line_values {
+        pub bits: u64,
+        pub mask: u64
+    }
+
+    /// `value` is the kernel's union of `flags`, `values` and `debounce_period_us`
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Default, Copy, Clone)]
+    #[repr(C)]
+    pub struct gpio_v2_line_attribute {
+        pub id: u32,
+        pub padding: u32,
+        pub value: u64
+    }
+
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Default, Copy, Clone)]
+    #[repr(C)]
+    pub struct gpio_v2_line_config_attribute {
+        pub attr: gpio_v2_line_attribute,
+        pub mask: u64
+    }
+
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Default, Copy, Clone)]
+    #[repr(C)]
+    pub struct gpio_v2_line_config {
+        pub flags: u64,
+        pub num_attrs: u32,
+        pub padding: [u32; 5],
+        pub attrs: [gpio_v2_line_config_attribute; GPIO_V2_LINE_NUM_ATTRS_MAX]
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct gpio_v2_line_request {
+        pub offsets: [u32; GPIO_V2_LINES_MAX],
+        pub consumer: [c_char; GPIO_MAX_NAME_SIZE],
+        pub config: gpio_v2_line_config,
+        pub num_lines: u32,
+        pub event_buffer_size: u32,
+        pub padding: [u32; 5],
+        pub fd: i32
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct gpio_v2_line_info {
+        pub name: [c_char; GPIO_MAX_NAME_SIZE],
+        pub consumer: [c_char; GPIO_MAX_NAME_SIZE],
+        pub offset: u32,
+        pub num_attrs: u32,
+        pub flags: u64,
+        pub attrs: [gpio_v2_line_attribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+        pub padding: [u32; 4]
+    }
+
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Default, Copy, Clone)]
+    #[repr(C)]
+    pub struct gpio_v2_line_event {
+        pub timestamp_ns: u64,
+        pub id: u32,
+        pub offset: u32,
+        pub seqno: u32,
+        pub line_seqno: u32,
+        pub padding: [u32; 6]
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    pub struct gpio_v2_line_info_changed {
+        pub info: gpio_v2_line_info,
+        pub timestamp_ns: u64,
+        pub event_type: u32,
+        pub padding: [u32; 5]
+    }
+
+    // The ioctl numbers encode the struct sizes, so make sure they match the uAPI
+    const _: () = assert!(std::mem::size_of::<gpiochip_info>() == 68);
+    const _: () = assert!(std::mem::size_of::<gpio_v2_line_config>() == 272);
+    const _: () = assert!(std::mem::size_of::<gpio_v2_line_request>() == 592);
+    const _: () = assert!(std::mem::size_of::<gpio_v2_line_info>() == 256);
+    const _: () = assert!(std::mem::size_of::<gpio_v2_line_info_changed>() == 288);
+
+    ioctl_read!(get_chipinfo, GPIO_IOC_MAGIC, GPIO_IOC_NR_GET_CHIPINFO, gpiochip_info);
+    ioctl_readwrite!(get_lineinfo, GPIO_IOC_MAGIC, GPIO_V2_IOC_NR_GET_LINEINFO, gpio_v2_line_info);
+    ioctl_readwrite!(get_lineinfo_watch, GPIO_IOC_MAGIC, GPIO_V2_IOC_NR_GET_LINEINFO_WATCH, gpio_v2_line_info);
+    ioctl_readwrite!(get_lineinfo_unwatch, GPIO_IOC_MAGIC, GPIO_IOC_NR_GET_LINEINFO_UNWATCH, u32);
+    ioctl_readwrite!(get_line, GPIO_IOC_MAGIC, GPIO_V2_IOC_NR_GET_LINE, gpio_v2_line_request);
+    ioctl_readwrite!(set_config, GPIO_IOC_MAGIC, GPIO_V2_IOC_NR_LINE_SET_CONFIG, gpio_v2_line_config);
+    ioctl_readwrite!(get_values, GPIO_IOC_MAGIC, GPIO_V2_IOC_NR_LINE_GET_VALUES, gpio_v2_line_values);
+    ioctl_readwrite!(set_values, GPIO_IOC_MAGIC, GPIO_V2_IOC_NR_LINE_SET_VALUES, gpio_v2_line_values);
+
+    /// Convert a nul-terminated kernel string
+    pub fn c_str(buf: &[c_char]) -> String {
+        let bytes: Vec<u8> = buf.iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as u8)
+            .collect();
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Copy `s` into a kernel string buffer, always leaving a trailing nul
+    pub fn copy_c_str(buf: &mut [c_char], s: &str) {
+        for (dst, src) in buf.iter_mut().take(GPIO_MAX_NAME_SIZE - 1).zip(s.bytes()) {
+            *dst = src as c_char;
+        }
+    }
+
+    pub fn line_flags(config: &LineConfig) -> u64 {
+        let mut flags = match config.direction {
+            Direction::In => GPIO_V2_LINE_FLAG_INPUT,
+            Direction::Out | Direction::Low | Direction::High => GPIO_V2_LINE_FLAG_OUTPUT
+        };
+
+        if config.active_low {
+            flags |= GPIO_V2_LINE_FLAG_ACTIVE_LOW;
+        }
+
+        flags |= match config.edge {
+            Edge::None => 0,
+            Edge::Rising => GPIO_V2_LINE_FLAG_EDGE_RISING,
+            Edge::Falling => GPIO_V2_LINE_FLAG_EDGE_FALLING,
+            Edge::Both => GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING
+        };
+
+        flags |= match config.bias {
+            Bias::AsIs => 0,
+            Bias::PullUp => GPIO_V2_LINE_FLAG_BIAS_PULL_UP,
+            Bias::PullDown => GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN,
+            Bias::Disabled => GPIO_V2_LINE_FLAG_BIAS_DISABLED
+        };
+
+        flags |= match config.drive {
+            Drive::PushPull => 0,
+            Drive::OpenDrain => GPIO_V2_LINE_FLAG_OPEN_DRAIN,
+            Drive::OpenSource => GPIO_V2_LINE_FLAG_OPEN_SOURCE
+        };
+
+        flags
+    }
+
+    /// Reject combinations the kernel would refuse with a bare EINVAL
+    fn validate(config: &LineConfig) -> Result<()> {
+        let input = config.direction == Direction::In;
+
+        if !input && config.edge != Edge::None {
+            return Err(Error::InvalidArgument("Edge detection is only supported on inputs".to_owned()))
+        }
+
+        if input && config.drive != Drive::PushPull {
+            return Err(Error::InvalidArgument(format!("{:?} drive is only supported on outputs", config.drive)))
+        }
+
+        if let Some(debounce) = config.debounce {
+            if !input {
+                return Err(Error::InvalidArgument("Debounce is only supported on inputs".to_owned()))
+            }
+
+            if debounce.as_micros() > u128::from(u32::MAX) {
+                return Err(Error::InvalidArgument(format!("Debounce period {:?} is too long", debounce)))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The debounce period goes in the `u32` member of the attribute union
+    fn debounce_value(debounce: Duration) -> u64 {
+        let us = debounce.as_micros() as u64;
+
+        if cfg!(target_endian = "big") {
+            us << 32
+        } else {
+            us
+        }
+    }
+
+    /// The inverse of `debounce_value`
+    fn debounce_period(value: u64) -> Duration {
+        let us = if cfg!(target_endian = "big") {
+            value >> 32
+        } else {
+            value & 0xffff_ffff
+        };
+
+        Duration::from_micros(us)
+    }
+
+    pub fn line_info(info: &gpio_v2_line_info) -> LineInfo {
+        let flags = info.flags;
+        let has = |flag: u64| flags & flag != 0;
+
+        let debounce = info.attrs.iter()
+            .take(info.num_attrs as usize)
+            .find(|attr| attr.id == GPIO_V2_LINE_ATTR_ID_DEBOUNCE)
+            .map(|attr| debounce_period(attr.value));
+
+        LineInfo {
+            offset: info.offset,
+            name: c_str(&info.name),
+            consumer: c_str(&info.consumer),
+            used: has(GPIO_V2_LINE_FLAG_USED),
+            direction: if has(GPIO_V2_LINE_FLAG_OUTPUT) { Direction::Out } else { Direction::In },
+            active_low: has(GPIO_V2_LINE_FLAG_ACTIVE_LOW),
+            edge: match (has(GPIO_V2_LINE_FLAG_EDGE_RISING), has(GPIO_V2_LINE_FLAG_EDGE_FALLING)) {
+                (true, true) => Edge::Both,
+                (true, false) => Edge::Rising,
+                (false, true) => Edge::Falling,
+                (false, false) => Edge::None
+            },
+            bias: if has(GPIO_V2_LINE_FLAG_BIAS_PULL_UP) {
+                Bias::PullUp
+            } else if has(GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN) {
+                Bias::PullDown
+            } else if has(GPIO_V2_LINE_FLAG_BIAS_DISABLED) {
+                Bias::Disabled
+            } else {
+                Bias::AsIs
+            },
+            drive: if has(GPIO_V2_LINE_FLAG_OPEN_DRAIN) {
+                Drive::OpenDrain
+            } else if has(GPIO_V2_LINE_FLAG_OPEN_SOURCE) {
+                Drive::OpenSource
+            } else {
+                Drive::PushPull
+            },
+            debounce
+        }
+    }
+
+    /// Classify kernel errors from line requests
+    pub fn request_error(e: io::Error) -> Error {
+        match e.raw_os_error() {
+            Some(libc::ENOTTY) => Error::UnsupportedFeature(
+                "GPIO v2 character device uAPI (Linux 5.10+)".to_owned()
+            ),
+            // ENOTSUPP is kernel internal but still leaks out of some drivers
+            Some(libc::EOPNOTSUPP) | Some(524) => Error::UnsupportedFeature(
+                format!("line configuration ({})", e)
+            ),
+            Some(libc::EINVAL) => Error::InvalidArgument(
+                format!("The line configuration was rejected by the kernel: {}", e)
+            ),
+            Some(libc::EBUSY) => Error::Busy(
+                format!("The line is already requested: {}", e)
+            ),
+            _ => Error::Io(e)
+        }
+    }
+
+    /// Read a single event, the kernel never splits them across reads
+    pub fn read_event(mut file: &File, buf: &mut [u8]) -> Result<()> {
+        let n = file.read(buf)?;
+
+        if n != buf.len() {
+            return Err(Error::InvalidEvent(format!("Short event read, {} of {} bytes", n, buf.len())))
+        }
+
+        Ok(())
+    }
+
+    /// Build a line config from one `LineConfig` per requested line
+    ///
+    /// The first line's flags become the default, lines that differ get a
+    /// flags attribute, and initial output values share a single attribute.
+    pub fn line_config(configs: &[LineConfig]) -> Result<gpio_v2_line_config> {
+        if configs.is_empty() || configs.len() > GPIO_V2_LINES_MAX {
+            return Err(Error::InvalidArgument(
+                format!("Between 1 and {} lines can be configured, got {}", GPIO_V2_LINES_MAX, configs.len())
+            ))
+        }
+
+        let mut line_config = gpio_v2_line_config {
+            flags: line_flags(&configs[0]),
+            ..Default::default()
+        };
+
+        let mut attrs: Vec<gpio_v2_line_config_attribute> = Vec::new();
+        let mut values_mask = 0u64;
+        let mut values = 0u64;
+
+        for (i, config) in configs.iter().enumerate() {
+            let bit = 1u64 << i;
+
+            validate(config)?;
+
+            let flags = line_flags(config);
+            if flags != line_config.flags {
+                match attrs.iter_mut().find(|a| a.attr.id == GPIO_V2_LINE_ATTR_ID_FLAGS && a.attr.value == flags) {
+                    Some(attr) => attr.mask |= bit,
+                    None => attrs.push(attribute(GPIO_V2_LINE_ATTR_ID_FLAGS, flags, bit))
+                }
+            }
+
+            if let Some(debounce) = config.debounce {
+                let value = debounce_value(debounce);
+                match attrs.iter_mut().find(|a| a.attr.id == GPIO_V2_LINE_ATTR_ID_DEBOUNCE && a.attr.value == value) {
+                    Some(attr) => attr.mask |= bit,
+                    None => attrs.push(attribute(GPIO_V2_LINE_ATTR_ID_DEBOUNCE, value, bit))
+                }
+            }
+
+            match config.direction {
+                Direction::Low => values_mask |= bit,
+                Direction::High => {
+                    values_mask |= bit;
+                    values |= bit;
+                }
+                _ => ()
+            }
+        }
+
+        if values_mask != 0 {
+            attrs.push(attribute(GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES, values, values_mask));
+        }
+
+        if attrs.len() > GPIO_V2_LINE_NUM_ATTRS_MAX {
+            return Err(Error::InvalidArgument(
+                format!("Too many distinct line configurations, at most {} attributes are supported", GPIO_V2_LINE_NUM_ATTRS_MAX)
+            ))
+        }
+
+        line_config.num_attrs = attrs.len() as u32;
+        line_config.attrs[..attrs.len()].copy_from_slice(&attrs);
+
+        Ok(line_config)
+    }
+
+    fn attribute(id: u32, value: u64, mask: u64) -> gpio_v2_line_config_attribute {
+        gpio_v2_line_config_attribute {
+            attr: gpio_v2_line_attribute {
+                id,
+                padding: 0,
+                value
+            },
+            mask
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn input() -> LineConfig {
+            LineConfig::default()
+        }
+
+        fn output(direction: Direction) -> LineConfig {
+            LineConfig { direction, ..Default::default() }
+        }
+
+        fn attrs(config: &gpio_v2_line_config) -> Vec<(u32, u64, u64)> {
+            config.attrs[..config.num_attrs as usize].iter()
+                .map(|a| (a.attr.id, a.attr.value, a.mask))
+                .collect()
+        }
+
+        #[test]
+        fn identical_lines_share_the_default_flags() {
+            let config = line_config(&[input(), input(), input()]).unwrap();
+
+            assert_eq!(config.flags, GPIO_V2_LINE_FLAG_INPUT);
+            assert_eq!(config.num_attrs, 0);
+        }
+
+        #[test]
+        fn differing_lines_get_masked_attributes() {
+            let pull_up = LineConfig { bias: Bias::PullUp, ..input() };
+            let config = line_config(&[
+                input(),
+                pull_up,
+                output(Direction::High),
+                pull_up,
+                output(Direction::Low)
+            ]).unwrap();
+
+            assert_eq!(config.flags, GPIO_V2_LINE_FLAG_INPUT);
+            assert_eq!(attrs(&config), [
+                (GPIO_V2_LINE_ATTR_ID_FLAGS, GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_BIAS_PULL_UP, 0b01010),
+                (GPIO_V2_LINE_ATTR_ID_FLAGS, GPIO_V2_LINE_FLAG_OUTPUT, 0b10100),
+                (GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES, 0b00100, 0b10100)
+            ]);
+        }
+
+        #[test]
+        fn outputs_without_an_initial_value_are_left_out_of_the_values() {
+            let config = line_config(&[output(Direction::Out), output(Direction::High)]).unwrap();
+
+            assert_eq!(config.flags, GPIO_V2_LINE_FLAG_OUTPUT);
+            assert_eq!(attrs(&config), [(GPIO_V2_LINE_ATTR_ID_OUTPUT_VALUES, 0b10, 0b10)]);
+        }
+
+        #[test]
+        fn too_many_distinct_attributes_are_rejected() {
+            let configs: Vec<LineConfig> = (1..=GPIO_V2_LINE_NUM_ATTRS_MAX as u64 + 1)
+                .map(|us| LineConfig { debounce: Some(Duration::from_micros(us)), ..input() })
+                .collect();
+
+            assert!(line_config(&configs[..GPIO_V2_LINE_NUM_ATTRS_MAX]).is_ok());
+            assert!(matches!(line_config(&configs), Err(Error::InvalidArgument(_))));
+        }
+
+        #[test]
+        fn line_counts_outside_the_uapi_limits_are_rejected() {
+            assert!(matches!(line_config(&[]), Err(Error::InvalidArgument(_))));
+            assert!(matches!(line_config(&[input(); GPIO_V2_LINES_MAX + 1]), Err(Error::InvalidArgument(_))));
+        }
+
+        fn as_u32(value: u64) -> u64 {
+            if cfg!(target_endian = "big") {
+                value << 32
+            } else {
+                value
+            }
+        }
+
+        #[test]
+        fn debounce_periods_are_whole_microseconds() {
+            assert_eq!(debounce_value(Duration::from_millis(5)), as_u32(5000));
+            assert_eq!(debounce_value(Duration::from_nanos(1999)), as_u32(1));
+            assert_eq!(debounce_value(Duration::from_micros(u64::from(u32::MAX))), as_u32(u64::from(u32::MAX)));
+            assert_eq!(debounce_period(debounce_value(Duration::from_micros(1234))), Duration::from_micros(1234));
+        }
+
+        #[test]
+        fn debounce_periods_beyond_u32_microseconds_are_rejected() {
+            let longest = Duration::from_micros(u64::from(u32::MAX));
+            let config = |debounce| LineConfig { debounce: Some(debounce), ..input() };
+
+            assert!(validate(&config(longest)).is_ok());
+            assert!(matches!(validate(&config(longest + Duration::from_micros(1))), Err(Error::InvalidArgument(_))));
+            assert!(matches!(line_config(&[config(Duration::from_secs(5000))]), Err(Error::InvalidArgument(_))));
+        }
+
+        #[test]
+        fn input_and_output_settings_are_checked_against_the_direction() {
+            let debounced = Some(Duration::from_millis(1));
+
+            assert!(validate(&LineConfig { edge: Edge::Both, bias: Bias::PullUp, debounce: debounced, ..input() }).is_ok());
+            assert!(validate(&LineConfig { drive: Drive::OpenDrain, bias: Bias::PullUp, ..output(Direction::High) }).is_ok());
+
+            for config in [
+                LineConfig { edge: Edge::Rising, ..output(Direction::Out) },
+                LineConfig { debounce: debounced, ..output(Direction::Low) },
+                LineConfig { drive: Drive::OpenDrain, ..input() },
+                LineConfig { drive: Drive::OpenSource, ..input() }
+            ] {
+                assert!(matches!(validate(&config), Err(Error::InvalidArgument(_))), "{:?}", config);
+            }
+        }
+
+        fn c_string(s: &str) -> [c_char; GPIO_MAX_NAME_SIZE] {
+            let mut buf = [0; GPIO_MAX_NAME_SIZE];
+            copy_c_str(&mut buf, s);
+            buf
+        }
+
+        fn raw_info(flags: u64, attrs: &[(u32, u64)]) -> gpio_v2_line_info {
+            let mut info: gpio_v2_line_info = unsafe { std::mem::zeroed() };
+            info.name = c_string("GPIO17");
+            info.consumer = c_string("button");
+            info.offset = 17;
+            info.flags = flags;
+            info.num_attrs = attrs.len() as u32;
+            for (attr, &(id, value)) in info.attrs.iter_mut().zip(attrs) {
+                *attr = gpio_v2_line_attribute { id, padding: 0, value };
+            }
+            info
+        }
+
+        #[test]
+        fn line_info_decodes_names_and_flags() {
+            let info = line_info(&raw_info(
+                GPIO_V2_LINE_FLAG_USED | GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_ACTIVE_LOW
+                    | GPIO_V2_LINE_FLAG_EDGE_FALLING | GPIO_V2_LINE_FLAG_BIAS_PULL_UP,
+                &[(GPIO_V2_LINE_ATTR_ID_DEBOUNCE, debounce_value(Duration::from_millis(5)))]
+            ));
+
+            assert_eq!(info, LineInfo {
+                offset: 17,
+                name: "GPIO17".to_owned(),
+                consumer: "button".to_owned(),
+                used: true,
+                direction: Direction::In,
+                active_low: true,
+                edge: Edge::Falling,
+                bias: Bias::PullUp,
+                drive: Drive::PushPull,
+                debounce: Some(Duration::from_millis(5))
+            });
+        }
+
+        #[test]
+        fn line_info_decodes_output_settings() {
+            let info = line_info(&raw_info(
+                GPIO_V2_LINE_FLAG_OUTPUT | GPIO_V2_LINE_FLAG_OPEN_SOURCE | GPIO_V2_LINE_FLAG_BIAS_DISABLED,
+                &[]
+            ));
+
+            assert_eq!(info.direction, Direction::Out);
+            assert_eq!((info.edge, info.bias, info.drive), (Edge::None, Bias::Disabled, Drive::OpenSource));
+            assert!(!info.used && !info.active_low);
+            assert_eq!(info.debounce, None);
+
+            let info = line_info(&raw_info(GPIO_V2_LINE_FLAG_OUTPUT | GPIO_V2_LINE_FLAG_OPEN_DRAIN | GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN, &[]));
+            assert_eq!((info.bias, info.drive), (Bias::PullDown, Drive::OpenDrain));
+        }
+
+        #[test]
+        fn line_info_only_reads_the_reported_attributes() {
+            let both = GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING;
+            let mut raw = raw_info(both, &[(GPIO_V2_LINE_ATTR_ID_FLAGS, 0), (GPIO_V2_LINE_ATTR_ID_DEBOUNCE, as_u32(10))]);
+
+            assert_eq!(line_info(&raw).edge, Edge::Both);
+            assert_eq!(line_info(&raw).debounce, Some(Duration::from_micros(10)));
+
+            raw.num_attrs = 1;
+            assert_eq!(line_info(&raw).debounce, None);
+        }
+    }
+}
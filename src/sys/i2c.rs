@@ -1,13 +1,15 @@
 #![allow(dead_code)]
 
-use std::io::{self, Read, Write};
+use std::io::{Read, Write};
 use std::fs::{File, OpenOptions};
 use std::marker::PhantomData;
 use std::os::raw::{c_ulong};
-use std::io::ErrorKind::InvalidData;
 use std::fmt;
 use std::os::unix::io::{AsRawFd, RawFd};
 
+use crate::{Error, Result};
+use super::ioctl::IoctlNumType;
+
 pub struct I2C {
     bus: u8,
     file: File,
@@ -17,11 +19,6 @@ pub struct I2C {
     _not_sync: PhantomData<*const ()>
 }
 
-#[cfg(target_env = "gnu")]
-type IoctlNumType = std::os::raw::c_ulong;
-#[cfg(target_env = "musl")]
-type IoctlNumType = std::os::raw::c_int;
-
 // Capabilities returned by REQ_FUNCS
 const FUNC_I2C: c_ulong = 0x01;
 const FUNC_10BIT_ADDR: c_ulong = 0x02;
@@ -102,11 +99,13 @@ struct RdwrRequest {
 }
 
 impl I2C {
-    pub fn new(bus: u8) -> io::Result<I2C> {
+    pub fn new(bus: u8) -> Result<I2C> {
+        let path = super::path(format!("dev/i2c-{}", bus));
         let file = OpenOptions::new()
             .read(true)
             .write(true)
-            .open(format!("/dev/i2c-{}", bus))?;
+            .open(&path)
+            .map_err(|e| Error::open(&path, e))?;
         
         let mut funcs: c_ulong = 0;
         syscall!(ioctl(file.as_raw_fd(), I2C_FUNCS as IoctlNumType, &mut funcs))?;
@@ -136,14 +135,13 @@ impl I2C {
         self.funcs
     }
 
-    pub fn clock_speed(&self) -> io::Result<u32> {
+    pub fn clock_speed(&self) -> Result<u32> {
         let mut buffer = [0u8; 4];
+        let path = super::path(format!("sys/class/i2c-adapter/i2c-{}/of_node/clock-frequency", self.bus));
 
-        File::open(format!(
-            "/sys/class/i2c-adapter/i2c-{}/of_node/clock-frequency",
-            self.bus
-        ))?
-        .read_exact(&mut buffer)?;
+        File::open(&path)
+            .map_err(|e| Error::open(&path, e))?
+            .read_exact(&mut buffer)?;
 
         Ok(u32::from(buffer[3])
             | (u32::from(buffer[2]) << 8)
@@ -151,13 +149,13 @@ impl I2C {
             | (u32::from(buffer[0]) << 24))
     }
 
-    pub fn set_slave_address(&mut self, slave_address: u16) -> io::Result<()> {
+    pub fn set_slave_address(&mut self, slave_address: u16) -> Result<()> {
         // Filter out invalid and unsupported addresses
         if (!self.addr_10bit
             && ((slave_address >> 3) == 0b1111 || slave_address > 0x7F))
             || (self.addr_10bit && slave_address > 0x03FF)
         {
-            return Err(io::Error::new(InvalidData, format!("Invalid slave address: {:?}", slave_address)))
+            return Err(Error::InvalidArgument(format!("Invalid slave address: {:?}", slave_address)))
         }
 
         // ioctl::set_slave_address(self.i2cdev.as_raw_fd(), c_ulong::from(slave_address))?;
@@ -168,7 +166,7 @@ impl I2C {
         Ok(())
     }
 
-    pub fn set_timeout(&self, timeout: u32) -> io::Result<()> {
+    pub fn set_timeout(&self, timeout: u32) -> Result<()> {
         // Contrary to the i2cdev documentation, this seems to
         // be used as a timeout for (part of?) the I2C transaction.
         // ioctl::set_timeout(self.i2cdev.as_raw_fd(), timeout as c_ulong)?;
@@ -183,7 +181,7 @@ impl I2C {
         Ok(())
     }
 
-    fn set_retries(&self, retries: u32) -> io::Result<()> {
+    fn set_retries(&self, retries: u32) -> Result<()> {
         // Set to private. While i2cdev implements retries, the underlying drivers don't.
         // ioctl::set_retries(self.i2cdev.as_raw_fd(), retries as c_ulong)?;
         syscall!(ioctl(self.file.as_raw_fd(), I2C_RETRIES as IoctlNumType, retries as c_ulong))?;
@@ -191,9 +189,9 @@ impl I2C {
         Ok(())
     }
 
-    pub fn set_addr_10bit(&mut self, addr_10bit: bool) -> io::Result<()> {
+    pub fn set_addr_10bit(&mut self, addr_10bit: bool) -> Result<()> {
         if !self.funcs.addr_10bit() {
-            return Err(io::Error::new(InvalidData, "FeatureNotSupported: addr_10bit".to_string()))
+            return Err(Error::UnsupportedFeature("addr_10bit".to_string()))
         }
         syscall!(ioctl(self.file.as_raw_fd(), I2C_TENBIT as IoctlNumType, addr_10bit as c_ulong))?;
 
@@ -202,21 +200,21 @@ impl I2C {
         Ok(())
     }
 
-    pub fn set_smbus_pec(&self, enable: bool) -> io::Result<()> {
+    pub fn set_smbus_pec(&self, enable: bool) -> Result<()> {
         syscall!(ioctl(self.file.as_raw_fd(), I2C_PEC as IoctlNumType, enable as c_ulong))?;
 
         Ok(())
     }
 
-    pub fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
-        self.file.read(buffer)
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        Ok(self.file.read(buffer)?)
     }
 
-    pub fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
-        self.file.write(buffer)
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize> {
+        Ok(self.file.write(buffer)?)
     }
 
-    pub fn write_read(&self, write_buffer: &[u8], read_buffer: &mut [u8]) -> io::Result<()> {
+    pub fn write_read(&self, write_buffer: &[u8], read_buffer: &mut [u8]) -> Result<()> {
         if write_buffer.is_empty() || read_buffer.is_empty() {
             return Ok(());
         }
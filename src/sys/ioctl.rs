@@ -0,0 +1,113 @@
+// Helpers for encoding Linux ioctl request numbers, shared by the
+// character device backends (spidev, gpiochip, ...).
+
+#![allow(dead_code)]
+#![allow(unused_macros)]
+
+#[cfg(target_env = "gnu")]
+pub(crate) type IoctlNumType = std::os::raw::c_ulong;
+#[cfg(target_env = "musl")]
+pub(crate) type IoctlNumType = std::os::raw::c_int;
+
+pub(crate) const NONE: u8 = 0;
+pub(crate) const READ: u8 = 2;
+pub(crate) const WRITE: u8 = 1;
+const SIZEBITS: u8 = 14;
+const DIRBITS: u8 = 2;
+
+const NRBITS: IoctlNumType = 8;
+const TYPEBITS: IoctlNumType = 8;
+
+pub(crate) const NRSHIFT: IoctlNumType = 0;
+pub(crate) const TYPESHIFT: IoctlNumType = NRSHIFT + NRBITS as IoctlNumType;
+pub(crate) const SIZESHIFT: IoctlNumType = TYPESHIFT + TYPEBITS as IoctlNumType;
+pub(crate) const DIRSHIFT: IoctlNumType = SIZESHIFT + SIZEBITS as IoctlNumType;
+
+pub(crate) const NRMASK: IoctlNumType = (1 << NRBITS) - 1;
+pub(crate) const TYPEMASK: IoctlNumType = (1 << TYPEBITS) - 1;
+pub(crate) const SIZEMASK: IoctlNumType = (1 << SIZEBITS) - 1;
+pub(crate) const DIRMASK: IoctlNumType = (1 << DIRBITS) - 1;
+
+macro_rules! ioc {
+    ($dir:expr, $ty:expr, $nr:expr, $sz:expr) => {{
+        use $crate::sys::ioctl::*;
+        (($dir as IoctlNumType & DIRMASK) << DIRSHIFT) |
+        (($ty as IoctlNumType & TYPEMASK) << TYPESHIFT) |
+        (($nr as IoctlNumType & NRMASK) << NRSHIFT) |
+        (($sz as IoctlNumType & SIZEMASK) << SIZESHIFT)
+    }}
+}
+
+macro_rules! request_code_none {
+    ($ty:expr, $nr:expr) => (ioc!($crate::sys::ioctl::NONE, $ty, $nr, 0))
+}
+
+macro_rules! request_code_read {
+    ($ty:expr, $nr:expr, $sz:expr) => (ioc!($crate::sys::ioctl::READ, $ty, $nr, $sz))
+}
+
+macro_rules! request_code_write {
+    ($ty:expr, $nr:expr, $sz:expr) => (ioc!($crate::sys::ioctl::WRITE, $ty, $nr, $sz))
+}
+
+macro_rules! request_code_readwrite {
+    ($ty:expr, $nr:expr, $sz:expr) => (ioc!($crate::sys::ioctl::READ | $crate::sys::ioctl::WRITE, $ty, $nr, $sz))
+}
+
+macro_rules! ioctl_read {
+    ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
+        $(#[$attr])*
+        pub fn $name(fd: std::os::raw::c_int, data: *mut $ty) -> std::io::Result<std::os::raw::c_int> {
+            syscall!(ioctl(fd, request_code_read!($ioty, $nr, std::mem::size_of::<$ty>()) as $crate::sys::ioctl::IoctlNumType, data))
+        }
+    )
+}
+
+macro_rules! ioctl_write_ptr {
+    ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
+        $(#[$attr])*
+        pub fn $name(fd: std::os::raw::c_int, data: *const $ty) -> std::io::Result<std::os::raw::c_int> {
+            syscall!(ioctl(fd, request_code_write!($ioty, $nr, std::mem::size_of::<$ty>()) as $crate::sys::ioctl::IoctlNumType, data))
+        }
+    )
+}
+
+macro_rules! ioctl_readwrite {
+    ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
+        $(#[$attr])*
+        pub fn $name(fd: std::os::raw::c_int, data: *mut $ty) -> std::io::Result<std::os::raw::c_int> {
+            syscall!(ioctl(fd, request_code_readwrite!($ioty, $nr, std::mem::size_of::<$ty>()) as $crate::sys::ioctl::IoctlNumType, data))
+        }
+    )
+}
+
+macro_rules! ioctl_read_buf {
+    ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
+        $(#[$attr])*
+        pub fn $name(fd: std::os::raw::c_int,
+                            data: &mut [$ty])
+                            -> std::io::Result<std::os::raw::c_int> {
+            syscall!(ioctl(fd, request_code_read!($ioty, $nr, data.len() * ::std::mem::size_of::<$ty>()) as $crate::sys::ioctl::IoctlNumType, data))
+        }
+    )
+}
+
+macro_rules! ioctl_write_buf {
+    ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
+        $(#[$attr])*
+        pub fn $name(fd: std::os::raw::c_int, data: &[$ty]) -> std::io::Result<std::os::raw::c_int> {
+            syscall!(ioctl(fd, request_code_write!($ioty, $nr, data.len() * ::std::mem::size_of::<$ty>()) as $crate::sys::ioctl::IoctlNumType, data.as_ptr()))
+        }
+    )
+}
+
+macro_rules! ioctl_readwrite_buf {
+    ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
+        $(#[$attr])*
+        pub fn $name(fd: std::os::raw::c_int,
+                            data: &mut [$ty])
+                            -> std::io::Result<std::os::raw::c_int> {
+            syscall!(ioctl(fd, request_code_readwrite!($ioty, $nr, data.len() * ::std::mem::size_of::<$ty>()) as $crate::sys::ioctl::IoctlNumType, data.as_mut_ptr()))
+        }
+    )
+}
@@ -10,7 +10,105 @@ macro_rules! syscall {
     }};
 }
 
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::env;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+#[macro_use]
+mod ioctl;
+
 pub mod gpio;
+pub mod gpiochip;
+#[cfg(feature = "async-tokio")]
+pub mod gpio_async;
 pub mod i2c;
 pub mod spi;
 pub mod pwm;
+
+static ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Resolve all sysfs and devfs paths relative to `root` instead of `/`
+///
+/// With a root of `/tmp/fake`, `Pin` uses `/tmp/fake/sys/class/gpio` and
+/// `SPI` opens `/tmp/fake/dev/spidev*`, which allows running against a
+/// generated fake tree. Affects handles opened after the call.
+pub fn set_root<P: Into<PathBuf>>(root: P) {
+    *ROOT.write().unwrap_or_else(|e| e.into_inner()) = Some(root.into());
+}
+
+/// The root directory set with `set_root`
+///
+/// Defaults to the `PERIPHERY_ROOT` environment variable if set, `/` otherwise.
+pub fn root() -> PathBuf {
+    if let Some(root) = ROOT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return root.clone()
+    }
+
+    env::var_os("PERIPHERY_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+/// Resolve `path`, given without a leading `/`, against `root()`
+pub(crate) fn path<P: AsRef<Path>>(path: P) -> PathBuf {
+    root().join(path)
+}
+
+/// Open the sysfs attribute `path` for writing
+///
+/// sysfs ignores the truncation, it is there for fake trees set up with
+/// `set_root` so that a short value does not leave the tail of a longer one.
+pub(crate) fn open_attribute(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|e| Error::attribute(path, e))
+}
+
+/// How long the export guards wait for udev to fix up attribute permissions
+pub(crate) const DEFAULT_EXPORT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Wait until all `paths` are writable by this process
+///
+/// udev applies its group and mode rules some time after sysfs attributes
+/// are created, until then they are only writable by root.
+///
+/// If the timeout expires the error for the first pending path is returned,
+/// usually `Error::PermissionDenied`.
+pub(crate) fn wait_writable<P: AsRef<Path>>(paths: &[P], timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+
+    loop {
+        let mut pending = None;
+
+        for path in paths {
+            let c_path = CString::new(path.as_ref().as_os_str().as_bytes())
+                .map_err(|_| Error::InvalidArgument(format!("{} contains a nul byte", path.as_ref().display())))?;
+
+            if unsafe { libc::access(c_path.as_ptr(), libc::W_OK) } == -1 {
+                pending = Some((path.as_ref(), io::Error::last_os_error()));
+                break;
+            }
+        }
+
+        match pending {
+            None => return Ok(()),
+            Some((path, e)) => {
+                if start.elapsed() >= timeout {
+                    return Err(Error::attribute(path, e))
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -2,13 +2,36 @@ use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::str::FromStr;
+use std::ops::Deref;
 use std::io::{self, Read, Write};
-use std::io::ErrorKind::Other;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// How long the kernel waits for a single capture before failing with ETIMEDOUT
+const CAPTURE_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct Pwm {
     chip: PwmChip,
-    number: u32
+    number: u32,
+    cdev: Option<PwmCdev>
+}
+
+/// The kernel interface a `Pwm` is driven through.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PwmBackend {
+    /// The `/sys/class/pwm/pwmchipN/pwmM` attributes
+    Sysfs,
+    /// The `/dev/pwmchipN` character device and its atomic waveform ioctls,
+    /// available since Linux 6.13
+    Cdev,
+    /// The character device if it can be used, sysfs otherwise. `Pwm::backend`
+    /// reports which one was picked.
+    Auto
 }
 
 #[derive(Debug)]
@@ -16,37 +39,167 @@ pub struct PwmChip {
     pub number: u32
 }
 
-#[derive(Debug)]
+/// A PWM chip found by `PwmChip::list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PwmChipInfo {
+    pub number: u32,
+    /// The number of channels, `npwm`
+    pub npwm: u32,
+    /// The name of the parent device, e.g. `7e20c000.pwm`
+    pub device: Option<String>,
+    /// The name of the driver bound to the parent device, e.g. `pwm-bcm2835`
+    pub driver: Option<String>,
+    /// The resolved sysfs path of the parent device
+    pub device_path: Option<PathBuf>,
+    /// The device tree node of the parent device, e.g. `/soc/pwm@7e20c000`
+    pub of_node: Option<PathBuf>
+}
+
+impl PwmChipInfo {
+    pub fn chip(&self) -> PwmChip {
+        PwmChip { number: self.number }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Polarity {
     Normal,
     Inverse
 }
 
+/// A complete PWM configuration, applied at once with `Pwm::apply`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PwmState {
+    pub period_ns: u64,
+    pub duty_cycle_ns: u64,
+    pub polarity: Polarity,
+    pub enabled: bool
+}
+
+/// An incoming signal measured by `Pwm::capture`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PwmCapture {
+    pub period_ns: u64,
+    pub duty_cycle_ns: u64
+}
+
+impl PwmCapture {
+    /// The measured frequency in Hz, or 0.0 without a period
+    pub fn frequency_hz(&self) -> f64 {
+        if self.period_ns == 0 {
+            return 0.0
+        }
+
+        1e9 / self.period_ns as f64
+    }
+
+    /// The measured duty cycle as a ratio in the range [0.0, 1.0]
+    pub fn duty_cycle(&self) -> f64 {
+        duty_ratio(self.duty_cycle_ns, self.period_ns)
+    }
+}
+
+/// The frequency and duty cycle interface shared by hardware `Pwm` channels
+/// and `soft_pwm::SoftPwm`, so code can drive either.
+pub trait PwmOutput {
+    fn enable(&self, enable: bool) -> Result<()>;
+    fn enabled(&self) -> Result<bool>;
+    fn period_ns(&self) -> Result<u64>;
+    fn set_period_ns(&self, period_ns: u64) -> Result<()>;
+    fn duty_cycle_ns(&self) -> Result<u64>;
+    fn set_duty_cycle_ns(&self, duty_cycle_ns: u64) -> Result<()>;
+    fn frequency_hz(&self) -> Result<f64>;
+    fn set_frequency_hz(&self, frequency_hz: f64) -> Result<()>;
+    fn duty_cycle(&self) -> Result<f64>;
+    fn set_duty_cycle(&self, duty_cycle: f64) -> Result<()>;
+}
+
 impl Pwm {
     /// Create a new Pwm wiht the provided chip/number
     ///
-    /// This function does not export the Pwm pin
-    pub fn new(chip: u32, number: u32) -> io::Result<Pwm> {
+    /// The channel is driven through sysfs, use `with_backend` for the
+    /// character device. This function does not export the Pwm pin
+    pub fn new(chip: u32, number: u32) -> Result<Pwm> {
+        Pwm::with_backend(chip, number, PwmBackend::Sysfs)
+    }
+
+    /// Create a new Pwm driven through a specific backend
+    ///
+    /// Channels requested through the character device do not show up in
+    /// sysfs, so `capture` is not available with `PwmBackend::Cdev`.
+    pub fn with_backend(chip: u32, number: u32, backend: PwmBackend) -> Result<Pwm> {
         let chip: PwmChip = PwmChip::new(chip)?;
+        let cdev = match backend {
+            PwmBackend::Sysfs => None,
+            PwmBackend::Cdev => Some(PwmCdev::open(&chip, number)?),
+            PwmBackend::Auto => PwmCdev::open(&chip, number).ok()
+        };
 
         Ok(Pwm {
             chip,
             number,
+            cdev
         })
     }
 
+    pub fn backend(&self) -> PwmBackend {
+        match self.cdev {
+            Some(_) => PwmBackend::Cdev,
+            None => PwmBackend::Sysfs
+        }
+    }
+
+    /// Whether the Pwm is exported, or requested through the character device
+    pub fn is_exported(&self) -> bool {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.lock().is_some()
+        }
+
+        fs::metadata(super::path(format!("sys/class/pwm/pwmchip{}/pwm{}", self.chip.number, self.number))).is_ok()
+    }
+
     /// Export the Pwm for use
-    pub fn export(&self) -> io::Result<()> {
+    ///
+    /// With the character device backend this requests the channel instead,
+    /// which fails if it is exported through sysfs.
+    pub fn export(&self) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.request()
+        }
+
         self.chip.export(self.number)
     }
 
+    /// Export the Pwm and wait up to `timeout` for its attributes to become writable
+    pub fn export_wait(&self, timeout: Duration) -> Result<()> {
+        self.export()?;
+        self.wait_writable(timeout)
+    }
+
+    fn wait_writable(&self, timeout: Duration) -> Result<()> {
+        // The character device has no attributes waiting on udev
+        if self.cdev.is_some() {
+            return Ok(())
+        }
+
+        self.chip.wait_writable(self.number, timeout)
+    }
+
     /// Unexport the PWM
-    pub fn unexport(&self) -> io::Result<()> {
+    pub fn unexport(&self) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.free()
+        }
+
         self.chip.unexport(self.number)
     }
 
     /// Enable/Disable the PWM Signal
-    pub fn enable(&self, enable: bool) -> io::Result<()> {
+    pub fn enable(&self, enable: bool) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.update(|state| state.enabled = enable)
+        }
+
         let mut enable_file = pwm_file_wo(&self.chip, self.number, "enable")?;
 
         let contents = if enable { "1" } else { "0" };
@@ -56,25 +209,40 @@ impl Pwm {
     }
 
     /// Query the state of enable for a given PWM pin
-    pub fn enabled(&self) -> io::Result<bool> {
-        pwm_file_parse::<u32>(&self.chip, self.number, "enable").map(|enable_state| {
-            match enable_state {
-                1 => true,
-                0 => false,
-                _ => panic!("enable != 1|0 should be unreachable"),
-            }
-        })
+    pub fn enabled(&self) -> Result<bool> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.state().map(|state| state.enabled)
+        }
+
+        let s = pwm_file_read(&self.chip, self.number, "enable")?;
+
+        match s.trim() {
+            "1" => Ok(true),
+            "0" => Ok(false),
+            _ => Err(Error::UnexpectedSysfsContent {
+                path: pwm_file_path(&self.chip, self.number, "enable"),
+                contents: s
+            }),
+        }
     }
 
     /// Get the currently configured duty_cycle in nanoseconds
-    pub fn duty_cycle_ns(&self) -> io::Result<u32> {
-        pwm_file_parse::<u32>(&self.chip, self.number, "duty_cycle")
+    pub fn duty_cycle_ns(&self) -> Result<u64> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.state().map(|state| state.duty_cycle_ns)
+        }
+
+        pwm_file_parse::<u64>(&self.chip, self.number, "duty_cycle")
     }
 
     /// The active time of the PWM signal
     ///
     /// Value is in nanoseconds and must be less than the period.
-    pub fn set_duty_cycle_ns(&self, duty_cycle_ns: u32) -> io::Result<()> {
+    pub fn set_duty_cycle_ns(&self, duty_cycle_ns: u64) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.update(|state| state.duty_cycle_ns = duty_cycle_ns)
+        }
+
         // we'll just let the kernel do the validation
         let mut duty_cycle_file = pwm_file_wo(&self.chip, self.number, "duty_cycle")?;
         duty_cycle_file.write_all(format!("{}", duty_cycle_ns).as_bytes())?;
@@ -82,94 +250,793 @@ impl Pwm {
     }
 
     /// Get the currently configured period in nanoseconds
-    pub fn period_ns(&self) -> io::Result<u32> {
-        pwm_file_parse::<u32>(&self.chip, self.number, "period")
+    pub fn period_ns(&self) -> Result<u64> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.state().map(|state| state.period_ns)
+        }
+
+        pwm_file_parse::<u64>(&self.chip, self.number, "period")
     }
 
     /// The period of the PWM signal in Nanoseconds
-    pub fn set_period_ns(&self, period_ns: u32) -> io::Result<()> {
+    pub fn set_period_ns(&self, period_ns: u64) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.update(|state| state.period_ns = period_ns)
+        }
+
         let mut period_file = pwm_file_wo(&self.chip, self.number, "period")?;
         period_file.write_all(format!("{}", period_ns).as_bytes())?;
         Ok(())
     }
+
+    /// Get the frequency of the PWM signal in Hz, 0.0 if no period is configured
+    pub fn frequency_hz(&self) -> Result<f64> {
+        let period_ns = self.period_ns()?;
+
+        if period_ns == 0 {
+            return Ok(0.0)
+        }
+
+        Ok(1e9 / period_ns as f64)
+    }
+
+    /// Set the frequency of the PWM signal in Hz
+    ///
+    /// The period is rounded to the nearest nanosecond and the duty cycle is
+    /// rescaled so the duty ratio stays the same.
+    pub fn set_frequency_hz(&self, frequency_hz: f64) -> Result<()> {
+        let period_ns = frequency_period_ns(frequency_hz)?;
+
+        let mut state = self.state()?;
+        let ratio = duty_ratio(state.duty_cycle_ns, state.period_ns);
+
+        state.period_ns = period_ns;
+        state.duty_cycle_ns = (ratio * period_ns as f64).round() as u64;
+
+        self.apply(&state)
+    }
+
+    /// Get the duty cycle as a ratio of the period, from 0.0 to 1.0
+    pub fn duty_cycle(&self) -> Result<f64> {
+        Ok(duty_ratio(self.duty_cycle_ns()?, self.period_ns()?))
+    }
+
+    /// Set the duty cycle as a ratio of the period, from 0.0 to 1.0
+    ///
+    /// The period has to be configured first, e.g. with `set_frequency_hz`.
+    pub fn set_duty_cycle(&self, duty_cycle: f64) -> Result<()> {
+        self.set_duty_cycle_ns(duty_cycle_ns(duty_cycle, self.period_ns()?)?)
+    }
+
+    /// Get the polarity of the PWM signal
+    pub fn polarity(&self) -> Result<Polarity> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.state().map(|state| state.polarity)
+        }
+
+        let s = pwm_file_read(&self.chip, self.number, "polarity")?;
+
+        match s.trim() {
+            "normal" => Ok(Polarity::Normal),
+            "inversed" => Ok(Polarity::Inverse),
+            _ => Err(Error::UnexpectedSysfsContent {
+                path: pwm_file_path(&self.chip, self.number, "polarity"),
+                contents: s
+            }),
+        }
+    }
+
+    /// Set the polarity of the PWM signal
+    ///
+    /// Most drivers only allow this while the PWM is disabled, `apply`
+    /// takes care of that.
+    pub fn set_polarity(&self, polarity: Polarity) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.update(|state| state.polarity = polarity)
+        }
+
+        let mut polarity_file = pwm_file_wo(&self.chip, self.number, "polarity")?;
+
+        let contents = match polarity {
+            Polarity::Normal => "normal",
+            Polarity::Inverse => "inversed"
+        };
+        polarity_file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Read the complete current configuration
+    pub fn state(&self) -> Result<PwmState> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.state()
+        }
+
+        Ok(PwmState {
+            period_ns: self.period_ns()?,
+            duty_cycle_ns: self.duty_cycle_ns()?,
+            polarity: self.polarity()?,
+            enabled: self.enabled()?
+        })
+    }
+
+    /// Move to `state`, ordering the writes so no intermediate step is rejected
+    ///
+    /// The output is disabled while the polarity changes, and the duty cycle
+    /// is shrunk before the period when the new period is shorter than the
+    /// current duty cycle. Attributes that already match are not written.
+    ///
+    /// With the character device backend the whole state is applied in a
+    /// single ioctl instead.
+    pub fn apply(&self, state: &PwmState) -> Result<()> {
+        if let Some(ref cdev) = self.cdev {
+            return cdev.apply(state)
+        }
+
+        validate_state(state)?;
+
+        let current = self.state()?;
+        let mut enabled = current.enabled;
+
+        if enabled && (!state.enabled || state.polarity != current.polarity) {
+            self.enable(false)?;
+            enabled = false;
+        }
+
+        if state.polarity != current.polarity {
+            self.set_polarity(state.polarity)?;
+        }
+
+        // The kernel rejects any write leaving duty_cycle > period
+        if state.period_ns < current.duty_cycle_ns {
+            self.set_duty_cycle_ns(state.duty_cycle_ns)?;
+            self.set_period_ns(state.period_ns)?;
+        } else {
+            if state.period_ns != current.period_ns {
+                self.set_period_ns(state.period_ns)?;
+            }
+            if state.duty_cycle_ns != current.duty_cycle_ns {
+                self.set_duty_cycle_ns(state.duty_cycle_ns)?;
+            }
+        }
+
+        if state.enabled && !enabled {
+            self.enable(true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Measure the period and duty cycle of the signal on the channel input
+    ///
+    /// Only some controllers support capture, others fail with
+    /// `Error::UnsupportedFeature`. Each attempt blocks for up to the
+    /// kernel's own capture timeout of one second. The first attempt is
+    /// always made, another one is only started while at least one second
+    /// of `timeout` is left, so the call returns `Error::Timeout` after at
+    /// most `timeout` or one second, whichever is longer.
+    pub fn capture(&self, timeout: Duration) -> Result<PwmCapture> {
+        if self.cdev.is_some() {
+            return Err(Error::UnsupportedFeature("capture through the PWM character device".to_string()))
+        }
+
+        let start = Instant::now();
+
+        loop {
+            match self.capture_once() {
+                Err(Error::Timeout) if start.elapsed() + CAPTURE_ATTEMPT_TIMEOUT <= timeout => continue,
+                result => return result
+            }
+        }
+    }
+
+    fn capture_once(&self) -> Result<PwmCapture> {
+        // Every read of the attribute starts a new capture
+        let s = match pwm_file_read(&self.chip, self.number, "capture") {
+            Err(Error::Io(ref e)) if e.raw_os_error() == Some(libc::ENOSYS)
+                || e.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+                return Err(Error::UnsupportedFeature("capture".to_string()))
+            }
+            result => result?
+        };
+
+        let mut values = s.split_whitespace().map(|v| v.parse::<u64>());
+
+        match (values.next(), values.next(), values.next()) {
+            (Some(Ok(period_ns)), Some(Ok(duty_cycle_ns)), None) => Ok(PwmCapture {
+                period_ns,
+                duty_cycle_ns
+            }),
+            _ => Err(Error::UnexpectedSysfsContent {
+                path: pwm_file_path(&self.chip, self.number, "capture"),
+                contents: s
+            }),
+        }
+    }
+}
+
+impl PwmOutput for Pwm {
+    fn enable(&self, enable: bool) -> Result<()> {
+        Pwm::enable(self, enable)
+    }
+
+    fn enabled(&self) -> Result<bool> {
+        Pwm::enabled(self)
+    }
+
+    fn period_ns(&self) -> Result<u64> {
+        Pwm::period_ns(self)
+    }
+
+    fn set_period_ns(&self, period_ns: u64) -> Result<()> {
+        Pwm::set_period_ns(self, period_ns)
+    }
+
+    fn duty_cycle_ns(&self) -> Result<u64> {
+        Pwm::duty_cycle_ns(self)
+    }
+
+    fn set_duty_cycle_ns(&self, duty_cycle_ns: u64) -> Result<()> {
+        Pwm::set_duty_cycle_ns(self, duty_cycle_ns)
+    }
+
+    fn frequency_hz(&self) -> Result<f64> {
+        Pwm::frequency_hz(self)
+    }
+
+    fn set_frequency_hz(&self, frequency_hz: f64) -> Result<()> {
+        Pwm::set_frequency_hz(self, frequency_hz)
+    }
+
+    fn duty_cycle(&self) -> Result<f64> {
+        Pwm::duty_cycle(self)
+    }
+
+    fn set_duty_cycle(&self, duty_cycle: f64) -> Result<()> {
+        Pwm::set_duty_cycle(self, duty_cycle)
+    }
+}
+
+/// A `Pwm` that stays exported for the lifetime of the guard.
+///
+/// On creation the channel is exported and the guard waits for udev to make
+/// its attributes writable. On drop the output is disabled, unless turned off
+/// with `set_disable_on_drop`, and the channel is unexported, so a panicking
+/// process does not leave it running. A channel that was already exported
+/// when the guard was created is left untouched.
+#[derive(Debug)]
+pub struct ExportedPwm {
+    pwm: Pwm,
+    disable_on_drop: bool,
+    /// Whether this guard exported the channel
+    unexport_on_drop: bool
+}
+
+impl ExportedPwm {
+    /// Export `pwm`, waiting up to one second for its attributes to become writable
+    pub fn new(pwm: Pwm) -> Result<ExportedPwm> {
+        ExportedPwm::with_timeout(pwm, super::DEFAULT_EXPORT_TIMEOUT)
+    }
+
+    /// Export `pwm`, waiting up to `timeout` for its attributes to become writable
+    pub fn with_timeout(pwm: Pwm, timeout: Duration) -> Result<ExportedPwm> {
+        let unexport_on_drop = !pwm.is_exported();
+        pwm.export()?;
+
+        // Created before waiting so the channel is unexported again on failure
+        let guard = ExportedPwm {
+            pwm,
+            disable_on_drop: unexport_on_drop,
+            unexport_on_drop
+        };
+
+        guard.pwm.wait_writable(timeout)?;
+
+        Ok(guard)
+    }
+
+    /// Whether to disable the output on drop, by default only if the guard
+    /// exported it
+    pub fn set_disable_on_drop(&mut self, disable_on_drop: bool) {
+        self.disable_on_drop = disable_on_drop;
+    }
+}
+
+impl Deref for ExportedPwm {
+    type Target = Pwm;
+
+    fn deref(&self) -> &Pwm {
+        &self.pwm
+    }
+}
+
+impl Drop for ExportedPwm {
+    fn drop(&mut self) {
+        if self.disable_on_drop {
+            let _ = self.pwm.enable(false);
+        }
+
+        if self.unexport_on_drop {
+            let _ = self.pwm.unexport();
+        }
+    }
 }
 
 impl PwmChip {
-    pub fn new(number: u32) -> io::Result<PwmChip> {
-        fs::metadata(&format!("/sys/class/pwm/pwmchip{}", number))?;
+    pub fn new(number: u32) -> Result<PwmChip> {
+        fs::metadata(super::path(format!("sys/class/pwm/pwmchip{}", number)))?;
         Ok(PwmChip { number })
     }
 
-    pub fn count(&self) -> io::Result<u32> {
-        let npwm_path = format!("/sys/class/pwm/pwmchip{}/npwm", self.number);
+    /// All PWM chips in the system, ordered by number
+    ///
+    /// Chips whose `npwm` cannot be read are skipped, only failing to read
+    /// `/sys/class/pwm` itself is an error.
+    pub fn list() -> Result<Vec<PwmChipInfo>> {
+        let class_path = super::path("sys/class/pwm");
+        let entries = match fs::read_dir(&class_path) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::open(&class_path, e))
+        };
+
+        let mut numbers = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            if let Some(number) = name.to_str()
+                .and_then(|name| name.strip_prefix("pwmchip"))
+                .and_then(|number| number.parse::<u32>().ok())
+            {
+                numbers.push(number);
+            }
+        }
+        numbers.sort_unstable();
+
+        Ok(numbers.into_iter().filter_map(|number| PwmChip { number }.info().ok()).collect())
+    }
+
+    /// Find the chip whose parent device or device tree node is called `name`
+    ///
+    /// `name` is matched against the device name (`7e20c000.pwm`), the full
+    /// device tree path (`/soc/pwm@7e20c000`) and the node name (`pwm@7e20c000`).
+    pub fn find(name: &str) -> Result<Option<PwmChip>> {
+        let matches = |info: &PwmChipInfo| {
+            info.device.as_deref() == Some(name)
+                || info.of_node.as_ref().is_some_and(|node| {
+                    node.as_os_str() == name || node.file_name().is_some_and(|node| node == name)
+                })
+        };
 
-        let mut s = String::new();
-        File::open(&npwm_path)?.read_to_string(&mut s)?;
+        Ok(PwmChip::list()?.iter().find(|info| matches(info)).map(PwmChipInfo::chip))
+    }
 
-        match s.parse::<u32>() {
+    /// Describe this chip, its parent device and driver
+    pub fn info(&self) -> Result<PwmChipInfo> {
+        let device = super::path(format!("sys/class/pwm/pwmchip{}/device", self.number));
+        let device_path = fs::canonicalize(&device).ok();
+        let file_name = |path: PathBuf| path.file_name().map(|name| name.to_string_lossy().into_owned());
+
+        let base = fs::canonicalize(super::path("sys/firmware/devicetree/base")).ok();
+        let of_node = fs::canonicalize(device.join("of_node")).ok().map(|node| {
+            match base.as_ref().and_then(|base| node.strip_prefix(base).ok()) {
+                Some(node) => Path::new("/").join(node),
+                None => node
+            }
+        });
+
+        Ok(PwmChipInfo {
+            number: self.number,
+            npwm: self.count()?,
+            device: device_path.clone().and_then(file_name),
+            driver: fs::canonicalize(device.join("driver")).ok().and_then(file_name),
+            device_path,
+            of_node
+        })
+    }
+
+    pub fn count(&self) -> Result<u32> {
+        let npwm_path = super::path(format!("sys/class/pwm/pwmchip{}/npwm", self.number));
+
+        let mut buf = Vec::new();
+        File::open(&npwm_path)
+            .map_err(|e| Error::open(&npwm_path, e))?
+            .read_to_end(&mut buf)?;
+        let s = String::from_utf8_lossy(&buf).into_owned();
+
+        match s.trim().parse::<u32>() {
             Ok(n) => Ok(n),
-            Err(_) => Err(io::Error::new(Other,
-                format!("Unexpected npwm contents: {:?}", s)
-            )),
+            Err(_) => Err(Error::UnexpectedSysfsContent {
+                path: npwm_path,
+                contents: s
+            }),
         }
     }
 
-    pub fn export(&self, number: u32) -> io::Result<()> {
+    pub fn export(&self, number: u32) -> Result<()> {
         // only export if not already exported
-        if fs::metadata(&format!(
-            "/sys/class/pwm/pwmchip{}/pwm{}",
+        if fs::metadata(super::path(format!(
+            "sys/class/pwm/pwmchip{}/pwm{}",
             self.number, number
-        ))
+        )))
         .is_err()
         {
-            let path = format!("/sys/class/pwm/pwmchip{}/export", self.number);
-            let _ = File::create(&path)?.write_all(format!("{}", number).as_bytes());
+            let path = super::path(format!("sys/class/pwm/pwmchip{}/export", self.number));
+            File::create(&path)
+                .map_err(|e| Error::open(&path, e))?
+                .write_all(format!("{}", number).as_bytes())?;
         }
         Ok(())
     }
 
-    pub fn unexport(&self, number: u32) -> io::Result<()> {
-        if fs::metadata(&format!(
-            "/sys/class/pwm/pwmchip{}/pwm{}",
+    /// Export a PWM channel and wait up to `timeout` for its attributes to become writable
+    ///
+    /// Right after export the attributes belong to root until udev applies
+    /// its rules, so non-root users should use this instead of `export`.
+    pub fn export_wait(&self, number: u32, timeout: Duration) -> Result<()> {
+        self.export(number)?;
+        self.wait_writable(number, timeout)
+    }
+
+    fn wait_writable(&self, number: u32, timeout: Duration) -> Result<()> {
+        let attributes: Vec<PathBuf> = ["period", "duty_cycle", "enable"].iter()
+            .map(|name| pwm_file_path(self, number, name))
+            .collect();
+
+        super::wait_writable(&attributes, timeout)
+    }
+
+    pub fn unexport(&self, number: u32) -> Result<()> {
+        if fs::metadata(super::path(format!(
+            "sys/class/pwm/pwmchip{}/pwm{}",
             self.number, number
-        ))
+        )))
         .is_ok()
         {
-            let path = format!("/sys/class/pwm/pwmchip{}/unexport", self.number);
-            let _ = File::create(&path)?.write_all(format!("{}", number).as_bytes());
+            let path = super::path(format!("sys/class/pwm/pwmchip{}/unexport", self.number));
+            File::create(&path)
+                .map_err(|e| Error::open(&path, e))?
+                .write_all(format!("{}", number).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// A channel driven through the `/dev/pwmchipN` character device.
+#[derive(Debug)]
+struct PwmCdev {
+    path: PathBuf,
+    file: File,
+    hwpwm: u32,
+    /// The configuration while the channel is requested. Disabled channels
+    /// have no period in hardware, so it is remembered here.
+    state: Mutex<Option<PwmState>>
+}
+
+impl PwmCdev {
+    /// Open the character device and check that it drives `hwpwm`
+    ///
+    /// Kernels before 6.13, and anything at the path that is not a PWM chip,
+    /// fail the waveform ioctls with ENOTTY, reported as
+    /// `Error::UnsupportedFeature`.
+    fn open(chip: &PwmChip, hwpwm: u32) -> Result<PwmCdev> {
+        let path = super::path(format!("dev/pwmchip{}", chip.number));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::open(&path, e))?;
+
+        // Reading the waveform requests the channel, so free it again until
+        // it is exported
+        let mut waveform = private::pwmchip_waveform {
+            hwpwm,
+            ..Default::default()
+        };
+        private::get_waveform(file.as_raw_fd(), &mut waveform).map_err(|e| match e.raw_os_error() {
+            Some(libc::ENOTTY) => Error::UnsupportedFeature(format!("PWM character device {}", path.display())),
+            Some(libc::EBUSY) => Error::Busy(format!("pwm{} of {} is in use", hwpwm, path.display())),
+            Some(libc::EINVAL) => Error::InvalidArgument(format!("{} has no pwm{}", path.display(), hwpwm)),
+            _ => Error::from(e)
+        })?;
+        private::free(file.as_raw_fd(), hwpwm)?;
+
+        Ok(PwmCdev {
+            path,
+            file,
+            hwpwm,
+            state: Mutex::new(None)
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<PwmState>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn request(&self) -> Result<()> {
+        let mut state = self.lock();
+
+        if state.is_some() {
+            return Ok(())
+        }
+
+        private::request(self.file.as_raw_fd(), self.hwpwm)?;
+
+        let mut waveform = private::pwmchip_waveform {
+            hwpwm: self.hwpwm,
+            ..Default::default()
+        };
+        private::get_waveform(self.file.as_raw_fd(), &mut waveform)?;
+
+        *state = Some(private::waveform_to_state(&waveform));
+
+        Ok(())
+    }
+
+    fn free(&self) -> Result<()> {
+        let mut state = self.lock();
+
+        if state.is_some() {
+            private::free(self.file.as_raw_fd(), self.hwpwm)?;
+            *state = None;
+        }
+
+        Ok(())
+    }
+
+    /// The last applied configuration
+    fn state(&self) -> Result<PwmState> {
+        self.lock().ok_or_else(|| Error::NotExported(self.path.clone()))
+    }
+
+    fn update<F: FnOnce(&mut PwmState)>(&self, f: F) -> Result<()> {
+        let mut state = self.state()?;
+        f(&mut state);
+        self.apply(&state)
+    }
+
+    fn apply(&self, state: &PwmState) -> Result<()> {
+        validate_state(state)?;
+
+        let mut current = self.lock();
+
+        if current.is_none() {
+            return Err(Error::NotExported(self.path.clone()))
         }
+
+        let waveform = private::state_to_waveform(self.hwpwm, state);
+        private::set_rounded_waveform(self.file.as_raw_fd(), &waveform)?;
+
+        *current = Some(*state);
+
         Ok(())
     }
 }
 
+fn validate_state(state: &PwmState) -> Result<()> {
+    if state.duty_cycle_ns > state.period_ns {
+        return Err(Error::InvalidArgument(format!(
+            "Duty cycle {}ns is longer than the period {}ns",
+            state.duty_cycle_ns, state.period_ns
+        )))
+    }
+
+    Ok(())
+}
+
+/// The nearest period in nanoseconds for `frequency_hz`
+pub(crate) fn frequency_period_ns(frequency_hz: f64) -> Result<u64> {
+    let period_ns = (1e9 / frequency_hz).round();
+
+    if !frequency_hz.is_finite() || !(1.0..=u64::MAX as f64).contains(&period_ns) {
+        return Err(Error::InvalidArgument(format!("Frequency {}Hz is out of range", frequency_hz)))
+    }
+
+    Ok(period_ns as u64)
+}
+
+/// The duty cycle in nanoseconds for the ratio `duty_cycle` of `period_ns`
+pub(crate) fn duty_cycle_ns(duty_cycle: f64, period_ns: u64) -> Result<u64> {
+    if !(0.0..=1.0).contains(&duty_cycle) {
+        return Err(Error::InvalidArgument(format!("Duty cycle {} is not between 0.0 and 1.0", duty_cycle)))
+    }
+
+    if period_ns == 0 {
+        return Err(Error::InvalidArgument("The period has to be set before the duty cycle".to_owned()))
+    }
+
+    Ok((duty_cycle * period_ns as f64).round() as u64)
+}
+
+/// `duty_cycle_ns / period_ns`, or 0.0 without a period
+pub(crate) fn duty_ratio(duty_cycle_ns: u64, period_ns: u64) -> f64 {
+    if period_ns == 0 {
+        return 0.0
+    }
+
+    duty_cycle_ns as f64 / period_ns as f64
+}
+
+/// The path of the specified entry name
+fn pwm_file_path(chip: &PwmChip, pin: u32, name: &str) -> PathBuf {
+    super::path(format!("sys/class/pwm/pwmchip{}/pwm{}/{}", chip.number, pin, name))
+}
+
 /// Open the specified entry name as a writable file
-fn pwm_file_wo(chip: &PwmChip, pin: u32, name: &str) -> io::Result<File> {
-    let f = OpenOptions::new().write(true).open(format!(
-        "/sys/class/pwm/pwmchip{}/pwm{}/{}",
-        chip.number, pin, name
-    ))?;
-    Ok(f)
+fn pwm_file_wo(chip: &PwmChip, pin: u32, name: &str) -> Result<File> {
+    super::open_attribute(&pwm_file_path(chip, pin, name))
 }
 
 /// Open the specified entry name as a readable file
-fn pwm_file_ro(chip: &PwmChip, pin: u32, name: &str) -> io::Result<File> {
-    let f = File::open(format!(
-        "/sys/class/pwm/pwmchip{}/pwm{}/{}",
-        chip.number, pin, name
-    ))?;
+fn pwm_file_ro(chip: &PwmChip, pin: u32, name: &str) -> Result<File> {
+    let path = pwm_file_path(chip, pin, name);
+    let f = File::open(&path).map_err(|e| Error::attribute(&path, e))?;
     Ok(f)
 }
 
-/// Get the u32 value from the given entry
-fn pwm_file_parse<T: FromStr>(chip: &PwmChip, pin: u32, name: &str) -> io::Result<T> {
-    let mut s = String::with_capacity(10);
+/// Read the raw contents of the given entry
+///
+/// Invalid UTF-8 is replaced rather than failing the read, so it shows up in
+/// `Error::UnexpectedSysfsContent` when parsing.
+fn pwm_file_read(chip: &PwmChip, pin: u32, name: &str) -> Result<String> {
+    let mut buf = Vec::with_capacity(10);
     let mut f = pwm_file_ro(chip, pin, name)?;
-    f.read_to_string(&mut s)?;
+    f.read_to_end(&mut buf)?;
+
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Parse the value of the given entry
+fn pwm_file_parse<T: FromStr>(chip: &PwmChip, pin: u32, name: &str) -> Result<T> {
+    let s = pwm_file_read(chip, pin, name)?;
 
     match s.trim().parse::<T>() {
         Ok(r) => Ok(r),
-        Err(_) => Err(io::Error::new(Other,
-            format!("Unexpeted value file contents: {:?}", s)
-        )),
+        Err(_) => Err(Error::UnexpectedSysfsContent {
+            path: pwm_file_path(chip, pin, name),
+            contents: s
+        }),
+    }
+}
+
+mod private {
+    use std::os::raw::{c_int, c_ulong};
+
+    use super::{Polarity, PwmState};
+
+    const PWM_IOC_MAGIC: u8 = 0x75;
+    const PWM_IOC_NR_REQUEST: u8 = 1;
+    const PWM_IOC_NR_FREE: u8 = 2;
+    const PWM_IOC_NR_GETWF: u8 = 4;
+    const PWM_IOC_NR_SETROUNDEDWF: u8 = 5;
+
+    /// A waveform is high for `duty_length_ns` starting `duty_offset_ns`
+    /// into each period, a period of 0 disables the output.
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Default, Copy, Clone)]
+    #[repr(C)]
+    pub struct pwmchip_waveform {
+        pub hwpwm: u32,
+        pub pad: u32,
+        pub period_length_ns: u64,
+        pub duty_length_ns: u64,
+        pub duty_offset_ns: u64
+    }
+
+    pub fn request(fd: c_int, hwpwm: u32) -> std::io::Result<c_int> {
+        syscall!(ioctl(fd, request_code_none!(PWM_IOC_MAGIC, PWM_IOC_NR_REQUEST) as crate::sys::ioctl::IoctlNumType, hwpwm as c_ulong))
+    }
+
+    pub fn free(fd: c_int, hwpwm: u32) -> std::io::Result<c_int> {
+        syscall!(ioctl(fd, request_code_none!(PWM_IOC_MAGIC, PWM_IOC_NR_FREE) as crate::sys::ioctl::IoctlNumType, hwpwm as c_ulong))
+    }
+
+    ioctl_readwrite!(get_waveform, PWM_IOC_MAGIC, PWM_IOC_NR_GETWF, pwmchip_waveform);
+    ioctl_write_ptr!(set_rounded_waveform, PWM_IOC_MAGIC, PWM_IOC_NR_SETROUNDEDWF, pwmchip_waveform);
+
+    /// Inverse polarity is expressed as a duty offset, the same way the
+    /// kernel translates between its legacy state and waveforms.
+    pub fn state_to_waveform(hwpwm: u32, state: &PwmState) -> pwmchip_waveform {
+        let mut waveform = pwmchip_waveform {
+            hwpwm,
+            ..Default::default()
+        };
+
+        if !state.enabled {
+            return waveform
+        }
+
+        waveform.period_length_ns = state.period_ns;
+
+        match state.polarity {
+            Polarity::Normal => {
+                waveform.duty_length_ns = state.duty_cycle_ns;
+            }
+            Polarity::Inverse => {
+                waveform.duty_length_ns = state.period_ns - state.duty_cycle_ns;
+                waveform.duty_offset_ns = state.duty_cycle_ns;
+            }
+        }
+
+        waveform
+    }
+
+    pub fn waveform_to_state(waveform: &pwmchip_waveform) -> PwmState {
+        let inverse = waveform.duty_offset_ns != 0
+            && waveform.duty_offset_ns.saturating_add(waveform.duty_length_ns) >= waveform.period_length_ns;
+
+        PwmState {
+            period_ns: waveform.period_length_ns,
+            duty_cycle_ns: if inverse {
+                waveform.period_length_ns.saturating_sub(waveform.duty_length_ns)
+            } else {
+                waveform.duty_length_ns
+            },
+            polarity: if inverse { Polarity::Inverse } else { Polarity::Normal },
+            enabled: waveform.period_length_ns != 0
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn state(period_ns: u64, duty_cycle_ns: u64, polarity: Polarity, enabled: bool) -> PwmState {
+            PwmState { period_ns, duty_cycle_ns, polarity, enabled }
+        }
+
+        #[test]
+        fn normal_polarity_starts_the_pulse_at_the_period() {
+            let waveform = state_to_waveform(3, &state(20_000, 5_000, Polarity::Normal, true));
+
+            assert_eq!(waveform.hwpwm, 3);
+            assert_eq!(waveform.period_length_ns, 20_000);
+            assert_eq!(waveform.duty_length_ns, 5_000);
+            assert_eq!(waveform.duty_offset_ns, 0);
+        }
+
+        #[test]
+        fn inverse_polarity_becomes_a_duty_offset() {
+            let waveform = state_to_waveform(0, &state(20_000, 5_000, Polarity::Inverse, true));
+
+            assert_eq!(waveform.period_length_ns, 20_000);
+            assert_eq!(waveform.duty_length_ns, 15_000);
+            assert_eq!(waveform.duty_offset_ns, 5_000);
+        }
+
+        #[test]
+        fn disabled_outputs_have_no_period() {
+            let waveform = state_to_waveform(1, &state(20_000, 5_000, Polarity::Inverse, false));
+
+            assert_eq!(waveform.hwpwm, 1);
+            assert_eq!(waveform.period_length_ns, 0);
+            assert_eq!(waveform.duty_length_ns, 0);
+            assert_eq!(waveform.duty_offset_ns, 0);
+            assert!(!waveform_to_state(&waveform).enabled);
+        }
+
+        #[test]
+        fn waveforms_round_trip() {
+            for s in [
+                state(20_000, 5_000, Polarity::Normal, true),
+                state(20_000, 0, Polarity::Normal, true),
+                state(20_000, 20_000, Polarity::Normal, true),
+                state(20_000, 5_000, Polarity::Inverse, true),
+                state(20_000, 19_999, Polarity::Inverse, true),
+            ] {
+                assert_eq!(waveform_to_state(&state_to_waveform(0, &s)), s);
+            }
+        }
+
+        #[test]
+        fn offsets_not_reaching_the_period_end_are_normal_polarity() {
+            let waveform = pwmchip_waveform {
+                period_length_ns: 20_000,
+                duty_length_ns: 5_000,
+                duty_offset_ns: 1_000,
+                ..Default::default()
+            };
+
+            assert_eq!(waveform_to_state(&waveform), state(20_000, 5_000, Polarity::Normal, true));
+        }
     }
 }
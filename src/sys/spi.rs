@@ -5,8 +5,14 @@
 use std::io::{self, Read, Write};
 use std::fs::{File, OpenOptions};
 use std::marker::PhantomData;
+use std::convert::TryFrom;
 use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not, RangeInclusive};
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{Error, Result};
 
 // 125.0 MHz   125000000
 // 62.5 MHz    62500000
@@ -69,14 +75,480 @@ impl fmt::Display for BitOrder {
     }
 }
 
+/// The full 32-bit spidev mode, a set of `SPI_*` flags.
+///
+/// Covers the clock mode, chip select and bit order bits also exposed by the
+/// individual `SPI` setters, plus 3-wire, loopback, dual and quad transfers.
+#[derive(PartialEq, Eq, Copy, Clone, Default, Hash)]
+pub struct SpiModeFlags {
+    bits: u32
+}
+
+impl SpiModeFlags {
+    /// Clock phase
+    pub const CPHA: SpiModeFlags = SpiModeFlags { bits: private::SPI_CPHA as u32 };
+    /// Clock polarity
+    pub const CPOL: SpiModeFlags = SpiModeFlags { bits: private::SPI_CPOL as u32 };
+    /// Chip select active high
+    pub const CS_HIGH: SpiModeFlags = SpiModeFlags { bits: private::SPI_CS_HIGH as u32 };
+    /// Least significant bit first
+    pub const LSB_FIRST: SpiModeFlags = SpiModeFlags { bits: private::SPI_LSB_FIRST as u32 };
+    /// MOSI and MISO shared on one wire
+    pub const THREE_WIRE: SpiModeFlags = SpiModeFlags { bits: private::SPI_3WIRE as u32 };
+    /// Loop MOSI back to MISO inside the controller
+    pub const LOOP: SpiModeFlags = SpiModeFlags { bits: private::SPI_LOOP as u32 };
+    /// No chip select, one device per bus
+    pub const NO_CS: SpiModeFlags = SpiModeFlags { bits: private::SPI_NO_CS as u32 };
+    /// The slave pulls the ready line low to pause
+    pub const READY: SpiModeFlags = SpiModeFlags { bits: private::SPI_READY as u32 };
+    /// Transmit on 2 wires
+    pub const TX_DUAL: SpiModeFlags = SpiModeFlags { bits: private::SPI_TX_DUAL };
+    /// Transmit on 4 wires
+    pub const TX_QUAD: SpiModeFlags = SpiModeFlags { bits: private::SPI_TX_QUAD };
+    /// Receive on 2 wires
+    pub const RX_DUAL: SpiModeFlags = SpiModeFlags { bits: private::SPI_RX_DUAL };
+    /// Receive on 4 wires
+    pub const RX_QUAD: SpiModeFlags = SpiModeFlags { bits: private::SPI_RX_QUAD };
+
+    const NAMES: [(SpiModeFlags, &'static str); 12] = [
+        (SpiModeFlags::CPHA, "CPHA"),
+        (SpiModeFlags::CPOL, "CPOL"),
+        (SpiModeFlags::CS_HIGH, "CS_HIGH"),
+        (SpiModeFlags::LSB_FIRST, "LSB_FIRST"),
+        (SpiModeFlags::THREE_WIRE, "THREE_WIRE"),
+        (SpiModeFlags::LOOP, "LOOP"),
+        (SpiModeFlags::NO_CS, "NO_CS"),
+        (SpiModeFlags::READY, "READY"),
+        (SpiModeFlags::TX_DUAL, "TX_DUAL"),
+        (SpiModeFlags::TX_QUAD, "TX_QUAD"),
+        (SpiModeFlags::RX_DUAL, "RX_DUAL"),
+        (SpiModeFlags::RX_QUAD, "RX_QUAD")
+    ];
+
+    pub const fn empty() -> SpiModeFlags {
+        SpiModeFlags { bits: 0 }
+    }
+
+    /// Wrap raw mode bits, including any without a constant here
+    pub const fn from_bits(bits: u32) -> SpiModeFlags {
+        SpiModeFlags { bits }
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub const fn contains(&self, other: SpiModeFlags) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    pub fn insert(&mut self, other: SpiModeFlags) {
+        self.bits |= other.bits;
+    }
+
+    pub fn remove(&mut self, other: SpiModeFlags) {
+        self.bits &= !other.bits;
+    }
+
+    pub fn set(&mut self, other: SpiModeFlags, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+
+    /// The clock mode encoded in the CPOL and CPHA bits
+    pub fn mode(&self) -> Mode {
+        match self.bits & 0x03 {
+            0x01 => Mode::Mode1,
+            0x02 => Mode::Mode2,
+            0x03 => Mode::Mode3,
+            _ => Mode::Mode0,
+        }
+    }
+}
+
+impl From<Mode> for SpiModeFlags {
+    fn from(mode: Mode) -> SpiModeFlags {
+        SpiModeFlags::from_bits(mode as u32)
+    }
+}
+
+impl BitOr for SpiModeFlags {
+    type Output = SpiModeFlags;
+
+    fn bitor(self, other: SpiModeFlags) -> SpiModeFlags {
+        SpiModeFlags { bits: self.bits | other.bits }
+    }
+}
+
+impl BitOrAssign for SpiModeFlags {
+    fn bitor_assign(&mut self, other: SpiModeFlags) {
+        self.bits |= other.bits;
+    }
+}
+
+impl BitAnd for SpiModeFlags {
+    type Output = SpiModeFlags;
+
+    fn bitand(self, other: SpiModeFlags) -> SpiModeFlags {
+        SpiModeFlags { bits: self.bits & other.bits }
+    }
+}
+
+impl Not for SpiModeFlags {
+    type Output = SpiModeFlags;
+
+    fn not(self) -> SpiModeFlags {
+        SpiModeFlags { bits: !self.bits }
+    }
+}
+
+impl fmt::Debug for SpiModeFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.bits;
+        let mut names = Vec::new();
+
+        for (flag, name) in SpiModeFlags::NAMES.iter() {
+            if self.contains(*flag) {
+                names.push(name.to_string());
+                rest &= !flag.bits;
+            }
+        }
+        if rest != 0 {
+            names.push(format!("{:#x}", rest));
+        }
+        if names.is_empty() {
+            names.push("empty".to_string());
+        }
+
+        write!(f, "SpiModeFlags({})", names.join(" | "))
+    }
+}
+
 pub type SpidevTransfer<'a, 'b> = private::spi_ioc_transfer<'a, 'b>;
 
+/// The complete configuration of an SPI device, applied at once by
+/// `SPI::open`, `SPI::with_options` or `SPI::apply`.
+///
+/// Unlike `SPI::new` nothing is left as the previous user of the device
+/// configured it. `SPI::options` reads the configuration back, so it can be
+/// saved and re-applied later.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpiOptions {
+    pub mode: Mode,
+    pub speed_hz: u32,
+    pub bits_per_word: u8,
+    pub bit_order: BitOrder,
+    pub ss_polarity: Polarity,
+    /// Further mode flags such as `SpiModeFlags::THREE_WIRE`. The clock
+    /// mode, chip select and bit order bits are taken from the fields above.
+    pub mode_flags: SpiModeFlags
+}
+
+impl Default for SpiOptions {
+    fn default() -> SpiOptions {
+        SpiOptions {
+            mode: Mode::Mode0,
+            speed_hz: 1_000_000,
+            bits_per_word: 8,
+            bit_order: BitOrder::MsbFirst,
+            ss_polarity: Polarity::ActiveLow,
+            mode_flags: SpiModeFlags::empty()
+        }
+    }
+}
+
+impl SpiOptions {
+    /// Mode 0 at 1 MHz with 8-bit words, MSB first and an active low chip select
+    pub fn new() -> SpiOptions {
+        SpiOptions::default()
+    }
+
+    pub fn mode(mut self, mode: Mode) -> SpiOptions {
+        self.mode = mode;
+        self
+    }
+
+    pub fn speed_hz(mut self, speed_hz: u32) -> SpiOptions {
+        self.speed_hz = speed_hz;
+        self
+    }
+
+    pub fn bits_per_word(mut self, bits_per_word: u8) -> SpiOptions {
+        self.bits_per_word = bits_per_word;
+        self
+    }
+
+    pub fn bit_order(mut self, bit_order: BitOrder) -> SpiOptions {
+        self.bit_order = bit_order;
+        self
+    }
+
+    pub fn ss_polarity(mut self, ss_polarity: Polarity) -> SpiOptions {
+        self.ss_polarity = ss_polarity;
+        self
+    }
+
+    pub fn mode_flags(mut self, mode_flags: SpiModeFlags) -> SpiOptions {
+        self.mode_flags = mode_flags;
+        self
+    }
+
+    /// The full 32-bit mode these options amount to
+    pub fn flags(&self) -> SpiModeFlags {
+        let mut flags = self.mode_flags & !(SpiModeFlags::CPOL | SpiModeFlags::CPHA);
+
+        flags |= SpiModeFlags::from(self.mode);
+        flags.set(SpiModeFlags::CS_HIGH, self.ss_polarity == Polarity::ActiveHigh);
+        flags.set(SpiModeFlags::LSB_FIRST, self.bit_order == BitOrder::LsbFirst);
+
+        flags
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.bits_per_word == 0 || self.bits_per_word > 32 {
+            return Err(Error::InvalidArgument(format!("SPI word size of {} bits is not between 1 and 32", self.bits_per_word)))
+        }
+
+        if self.speed_hz == 0 {
+            return Err(Error::InvalidArgument("SPI speed is 0Hz".to_string()))
+        }
+
+        Ok(())
+    }
+}
+
+/// The buffer element type for a range of word sizes.
+///
+/// spidev stores words of up to 8 bits in a `u8`, up to 16 bits in a native
+/// endian `u16` and up to 32 bits in a native endian `u32`, right aligned.
+pub trait SpiWord: Copy + private::Sealed {
+    /// The `bits_per_word` values carried by this type
+    const WORD_BITS: RangeInclusive<u8>;
+}
+
+impl SpiWord for u8 {
+    const WORD_BITS: RangeInclusive<u8> = 1..=8;
+}
+
+impl SpiWord for u16 {
+    const WORD_BITS: RangeInclusive<u8> = 9..=16;
+}
+
+impl SpiWord for u32 {
+    const WORD_BITS: RangeInclusive<u8> = 17..=32;
+}
+
+fn word_bytes<W: SpiWord>(words: &[W]) -> &[u8] {
+    // u8, u16 and u32 have no padding
+    unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, std::mem::size_of_val(words)) }
+}
+
+fn word_bytes_mut<W: SpiWord>(words: &mut [W]) -> &mut [u8] {
+    // u8, u16 and u32 have no padding and any bit pattern is valid
+    unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, std::mem::size_of_val(words)) }
+}
+
+/// The `SpiWord` type of a transfer's buffers
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WordType {
+    bits: RangeInclusive<u8>,
+    name: &'static str
+}
+
+impl WordType {
+    fn of<W: SpiWord>() -> WordType {
+        WordType {
+            bits: W::WORD_BITS,
+            name: std::any::type_name::<W>()
+        }
+    }
+
+    /// Fail unless words of `bits_per_word` are stored in this type
+    fn check(&self, bits_per_word: u8) -> Result<()> {
+        // 0 is the kernel's way of saying 8
+        let bits = match bits_per_word {
+            0 => 8,
+            bits => bits
+        };
+
+        if !self.bits.contains(&bits) {
+            return Err(Error::InvalidArgument(format!(
+                "{}-bit SPI words need a buffer of {}, not {}",
+                bits,
+                match bits { 1..=8 => "u8", 9..=16 => "u16", _ => "u32" },
+                self.name
+            )))
+        }
+
+        Ok(())
+    }
+}
+
+/// A sequence of transfers executed as one message by `SPI::execute`.
+///
+/// Transfers are added with `write`, `read` and `transfer`. The other
+/// methods modify the transfer added last. Invalid arguments are reported
+/// when the transaction is executed, so calls can be chained.
+///
+/// The buffers are `SpiWord`s matching the transfer's `bits_per_word`, or
+/// the device's if it is not overridden.
+#[derive(Debug, Default)]
+pub struct SpiTransaction<'a> {
+    transfers: Vec<SpidevTransfer<'a, 'a>>,
+    /// The buffer type of each transfer
+    words: Vec<WordType>,
+    error: Option<Error>
+}
+
+impl<'a> SpiTransaction<'a> {
+    pub fn new() -> SpiTransaction<'a> {
+        SpiTransaction {
+            transfers: Vec::new(),
+            words: Vec::new(),
+            error: None
+        }
+    }
+
+    /// Add a transfer sending `tx` and discarding what is received
+    pub fn write<W: SpiWord>(self, tx: &'a [W]) -> SpiTransaction<'a> {
+        self.push::<W>(SpidevTransfer::write(word_bytes(tx)))
+    }
+
+    /// Add a transfer receiving into `rx`, sending zeros
+    pub fn read<W: SpiWord>(self, rx: &'a mut [W]) -> SpiTransaction<'a> {
+        self.push::<W>(SpidevTransfer::read(word_bytes_mut(rx)))
+    }
+
+    /// Add a full duplex transfer, `tx` and `rx` have to be the same length
+    pub fn transfer<W: SpiWord>(self, tx: &'a [W], rx: &'a mut [W]) -> SpiTransaction<'a> {
+        self.push::<W>(SpidevTransfer::read_write(word_bytes(tx), word_bytes_mut(rx)))
+    }
+
+    /// Wait `delay` after the last transfer, before the next one or before
+    /// deselecting the device, at most 65535µs
+    pub fn delay(self, delay: Duration) -> SpiTransaction<'a> {
+        match u16::try_from(delay.as_micros()) {
+            Ok(us) => self.modify("delay", |transfer| transfer.delay_usecs = us),
+            Err(_) => self.fail(format!("SPI transfer delay {:?} is longer than 65535µs", delay))
+        }
+    }
+
+    /// Wait `delay` between the words of the last transfer, at most 255µs
+    pub fn word_delay(self, delay: Duration) -> SpiTransaction<'a> {
+        match u8::try_from(delay.as_micros()) {
+            Ok(us) => self.modify("word_delay", |transfer| transfer.word_delay_usecs = us),
+            Err(_) => self.fail(format!("SPI word delay {:?} is longer than 255µs", delay))
+        }
+    }
+
+    /// Clock the last transfer at `speed_hz` instead of the device default
+    pub fn speed_hz(self, speed_hz: u32) -> SpiTransaction<'a> {
+        self.modify("speed_hz", |transfer| transfer.speed_hz = speed_hz)
+    }
+
+    /// Use `bits_per_word` for the last transfer instead of the device default
+    ///
+    /// The transfer's buffers have to be of the matching `SpiWord` type.
+    pub fn bits_per_word(self, bits_per_word: u8) -> SpiTransaction<'a> {
+        if bits_per_word == 0 || bits_per_word > 32 {
+            return self.fail(format!("SPI word size of {} bits is not between 1 and 32", bits_per_word))
+        }
+
+        if let Some(Err(e)) = self.words.last().map(|words| words.check(bits_per_word)) {
+            return self.fail_with(e)
+        }
+
+        self.modify("bits_per_word", |transfer| transfer.bits_per_word = bits_per_word)
+    }
+
+    /// Toggle chip select after the last transfer
+    ///
+    /// Between transfers this deselects the device briefly, after the final
+    /// transfer it keeps the device selected until the next message.
+    pub fn cs_change(self) -> SpiTransaction<'a> {
+        self.modify("cs_change", |transfer| transfer.cs_change = 1)
+    }
+
+    /// Send the last transfer on 1, 2, 4 or 8 wires
+    ///
+    /// Anything above 1 also needs the matching `SpiModeFlags`.
+    pub fn tx_nbits(self, nbits: u8) -> SpiTransaction<'a> {
+        match check_nbits(nbits) {
+            Ok(()) => self.modify("tx_nbits", |transfer| transfer.tx_nbits = nbits),
+            Err(e) => self.fail(e)
+        }
+    }
+
+    /// Receive the last transfer on 1, 2, 4 or 8 wires
+    ///
+    /// Anything above 1 also needs the matching `SpiModeFlags`.
+    pub fn rx_nbits(self, nbits: u8) -> SpiTransaction<'a> {
+        match check_nbits(nbits) {
+            Ok(()) => self.modify("rx_nbits", |transfer| transfer.rx_nbits = nbits),
+            Err(e) => self.fail(e)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transfers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+
+    fn push<W: SpiWord>(mut self, transfer: Result<SpidevTransfer<'a, 'a>>) -> SpiTransaction<'a> {
+        match transfer {
+            Ok(transfer) => {
+                self.transfers.push(transfer);
+                self.words.push(WordType::of::<W>());
+                self
+            }
+            Err(e) => self.fail_with(e)
+        }
+    }
+
+    fn modify<F: FnOnce(&mut SpidevTransfer<'a, 'a>)>(mut self, name: &str, f: F) -> SpiTransaction<'a> {
+        match self.transfers.last_mut() {
+            Some(transfer) => f(transfer),
+            None => return self.fail(format!("SPI transaction {} set before any transfer", name))
+        }
+        self
+    }
+
+    /// Remember the first error for `SPI::execute`
+    fn fail(self, msg: String) -> SpiTransaction<'a> {
+        self.fail_with(Error::InvalidArgument(msg))
+    }
+
+    fn fail_with(mut self, e: Error) -> SpiTransaction<'a> {
+        self.error.get_or_insert(e);
+        self
+    }
+}
+
+fn check_nbits(nbits: u8) -> std::result::Result<(), String> {
+    match nbits {
+        1 | 2 | 4 | 8 => Ok(()),
+        _ => Err(format!("SPI transfer width of {} wires is not 1, 2, 4 or 8", nbits))
+    }
+}
+
 impl SPI {
-    pub fn new(bus: u8, slave: u8, speed_hz: u32, mode: Mode) -> io::Result<SPI> {
+    pub fn new(bus: u8, slave: u8, speed_hz: u32, mode: Mode) -> Result<SPI> {
+        let path = super::path(format!("dev/spidev{}.{}", bus, slave));
         let file = OpenOptions::new()
             .read(true)
             .write(true)
-            .open(format!("/dev/spidev{}.{}", bus, slave))?;
+            .open(&path)
+            .map_err(|e| Error::open(&path, e))?;
 
         let spi = SPI {
             file,
@@ -90,7 +562,64 @@ impl SPI {
         Ok(spi)
     }
 
-    pub fn mode(&self) -> io::Result<Mode> {
+    /// Open `/dev/spidev{bus}.{slave}` and apply `options`
+    pub fn with_options(bus: u8, slave: u8, options: &SpiOptions) -> Result<SPI> {
+        SPI::open(super::path(format!("dev/spidev{}.{}", bus, slave)), options)
+    }
+
+    /// Open the spidev device at `path` and apply `options`
+    pub fn open<P: AsRef<Path>>(path: P, options: &SpiOptions) -> Result<SPI> {
+        options.validate()?;
+
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| Error::open(path, e))?;
+
+        let spi = SPI {
+            file,
+            _not_sync: PhantomData
+        };
+
+        spi.apply(options)?;
+
+        Ok(spi)
+    }
+
+    /// Apply all of `options`
+    ///
+    /// Fails like `set_mode_flags` if the controller does not support a
+    /// mode flag.
+    pub fn apply(&self, options: &SpiOptions) -> Result<()> {
+        options.validate()?;
+
+        self.set_mode_flags(options.flags())?;
+        self.set_bits_per_word(options.bits_per_word)?;
+        self.set_speed_hz(options.speed_hz)?;
+
+        Ok(())
+    }
+
+    /// Read the complete current configuration
+    pub fn options(&self) -> Result<SpiOptions> {
+        let flags = self.mode_flags()?;
+
+        Ok(SpiOptions {
+            mode: flags.mode(),
+            speed_hz: self.speed_hz()?,
+            bits_per_word: match self.bits_per_word()? {
+                0 => 8,
+                bits => bits
+            },
+            bit_order: if flags.contains(SpiModeFlags::LSB_FIRST) { BitOrder::LsbFirst } else { BitOrder::MsbFirst },
+            ss_polarity: if flags.contains(SpiModeFlags::CS_HIGH) { Polarity::ActiveHigh } else { Polarity::ActiveLow },
+            mode_flags: flags & !(SpiModeFlags::CPOL | SpiModeFlags::CPHA | SpiModeFlags::CS_HIGH | SpiModeFlags::LSB_FIRST)
+        })
+    }
+
+    pub fn mode(&self) -> Result<Mode> {
         let mut mode: u8 = 0;
 
         private::get_mode_u8(self.file.as_raw_fd(), &mut mode)?;
@@ -103,7 +632,7 @@ impl SPI {
         })
     }
 
-    pub fn set_mode(&self, mode: Mode) -> io::Result<()> {
+    pub fn set_mode(&self, mode: Mode) -> Result<()> {
         let old_mode = self.mode()?;
 
         // Make sure we only replace the CPOL/CPHA bits
@@ -114,7 +643,7 @@ impl SPI {
         Ok(())
     }
 
-    pub fn speed_hz(&self) -> io::Result<u32> {
+    pub fn speed_hz(&self) -> Result<u32> {
         let mut speed_hz: u32 = 0;
 
         private::get_max_speed_hz(self.file.as_raw_fd(), &mut speed_hz)?;
@@ -122,13 +651,13 @@ impl SPI {
         Ok(speed_hz)
     }
 
-    pub fn set_speed_hz(&self, speed_hz: u32) -> io::Result<()> {
+    pub fn set_speed_hz(&self, speed_hz: u32) -> Result<()> {
         private::set_max_speed_hz(self.file.as_raw_fd(), &speed_hz)?;
 
         Ok(())
     }
 
-    pub fn bits_per_word(&self) -> io::Result<u8> {
+    pub fn bits_per_word(&self) -> Result<u8> {
         let mut bits_per_word: u8 = 0;
 
         private::get_bits_per_word(self.file.as_raw_fd(), &mut bits_per_word)?;
@@ -136,13 +665,13 @@ impl SPI {
         Ok(bits_per_word)
     }
 
-    pub fn set_bits_per_word(&self, size: u8) -> io::Result<()> {
+    pub fn set_bits_per_word(&self, size: u8) -> Result<()> {
         private::set_bits_per_word(self.file.as_raw_fd(), &size)?;
 
         Ok(())
     }
 
-    pub fn bit_order(&self) -> io::Result<BitOrder> {
+    pub fn bit_order(&self) -> Result<BitOrder> {
         let mut bit_order: u8 = 0;
 
         private::get_lsb_first(self.file.as_raw_fd(), &mut bit_order)?;
@@ -153,13 +682,13 @@ impl SPI {
         })
     }
 
-    pub fn set_bit_order(&self, bit_order: BitOrder) -> io::Result<()> {
+    pub fn set_bit_order(&self, bit_order: BitOrder) -> Result<()> {
         private::set_lsb_first(self.file.as_raw_fd(), &(bit_order as u8))?;
 
         Ok(())
     }
 
-    pub fn ss_polarity(&self) -> io::Result<Polarity> {
+    pub fn ss_polarity(&self) -> Result<Polarity> {
         let mut mode: u8 = 0;
 
         private::get_mode_u8(self.file.as_raw_fd(), &mut mode)?;
@@ -171,7 +700,7 @@ impl SPI {
         Ok(Polarity::ActiveHigh)
     }
 
-    pub fn set_ss_polarity(&self, polarity: Polarity) -> io::Result<()> {
+    pub fn set_ss_polarity(&self, polarity: Polarity) -> Result<()> {
         let mut mode: u8 = 0;
 
         private::get_mode_u8(self.file.as_raw_fd(), &mut mode)?;
@@ -187,15 +716,48 @@ impl SPI {
         Ok(())
     }
 
-    pub fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    /// Read the full 32-bit mode
+    pub fn mode_flags(&self) -> Result<SpiModeFlags> {
+        let mut mode: u32 = 0;
+
+        private::get_mode_u32(self.file.as_raw_fd(), &mut mode)?;
+
+        Ok(SpiModeFlags::from_bits(mode))
+    }
+
+    /// Replace the full 32-bit mode
+    ///
+    /// Fails with `Error::InvalidArgument` if the controller rejects a flag.
+    /// Controllers without dual or quad support silently drop those flags,
+    /// so the mode is read back and any missing flag is reported as
+    /// `Error::UnsupportedFeature`.
+    pub fn set_mode_flags(&self, flags: SpiModeFlags) -> Result<()> {
+        private::set_mode32(self.file.as_raw_fd(), &flags.bits()).map_err(|e| {
+            match e.raw_os_error() {
+                Some(libc::EINVAL) => Error::InvalidArgument(format!("SPI mode {:?} rejected by the controller", flags)),
+                _ => Error::from(e)
+            }
+        })?;
+
+        let applied = self.mode_flags()?;
+        let missing = flags & !applied;
+
+        if !missing.is_empty() {
+            return Err(Error::UnsupportedFeature(format!("SPI mode {:?}", missing)))
+        }
+
+        Ok(())
+    }
+
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
         Ok(self.file.read(buffer)?)
     }
 
-    pub fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize> {
         Ok(self.file.write(buffer)?)
     }
 
-    pub fn transfer(&self, transfer: &mut SpidevTransfer) -> io::Result<()> {
+    pub fn transfer(&self, transfer: &mut SpidevTransfer) -> Result<()> {
         // The kernel will directly modify the rx_buf of the SpidevTransfer
         // rx_buf if present, so there is no need to do any additional work
         private::spidev_transfer(self.file.as_raw_fd(), transfer)?;
@@ -203,11 +765,69 @@ impl SPI {
         Ok(())
     }
 
-    pub fn transfer_multiple(&self, transfers: &mut [SpidevTransfer]) -> io::Result<()> {
+    pub fn transfer_multiple(&self, transfers: &mut [SpidevTransfer]) -> Result<()> {
         private::spidev_transfer_buf(self.file.as_raw_fd(), transfers)?;
 
         Ok(())
     }
+
+    /// Receive `words`, which have to match the configured `bits_per_word`
+    pub fn read_words<W: SpiWord>(&self, words: &mut [W]) -> Result<()> {
+        self.check_word::<W>()?;
+        self.transfer(&mut SpidevTransfer::read(word_bytes_mut(words))?)
+    }
+
+    /// Send `words`, which have to match the configured `bits_per_word`
+    pub fn write_words<W: SpiWord>(&self, words: &[W]) -> Result<()> {
+        self.check_word::<W>()?;
+        self.transfer(&mut SpidevTransfer::write(word_bytes(words))?)
+    }
+
+    /// Send `tx` while receiving into `rx`, see `read_words`
+    pub fn transfer_words<W: SpiWord>(&self, tx: &[W], rx: &mut [W]) -> Result<()> {
+        if tx.len() != rx.len() {
+            return Err(Error::InvalidArgument(format!(
+                "SPI transfer buffers differ in length, {} words to send and {} to receive",
+                tx.len(), rx.len()
+            )))
+        }
+
+        self.check_word::<W>()?;
+        self.transfer(&mut SpidevTransfer::read_write(word_bytes(tx), word_bytes_mut(rx))?)
+    }
+
+    fn check_word<W: SpiWord>(&self) -> Result<()> {
+        WordType::of::<W>().check(self.bits_per_word()?)
+    }
+
+    /// Execute all transfers of `transaction` as a single message
+    ///
+    /// The device stays selected for the whole message, unless a transfer
+    /// asks for `cs_change`.
+    pub fn execute(&self, mut transaction: SpiTransaction<'_>) -> Result<()> {
+        if let Some(e) = transaction.error.take() {
+            return Err(e)
+        }
+
+        if transaction.transfers.is_empty() {
+            return Ok(())
+        }
+
+        let bits_per_word = self.bits_per_word()?;
+        for (transfer, words) in transaction.transfers.iter().zip(&transaction.words) {
+            if transfer.bits_per_word == 0 {
+                words.check(bits_per_word)?;
+            }
+        }
+
+        private::spidev_transfer_buf(self.file.as_raw_fd(), &transaction.transfers)
+            .map_err(|e| match e.raw_os_error() {
+                Some(libc::EINVAL) => Error::InvalidArgument("SPI transaction rejected by the controller".to_string()),
+                _ => Error::from(e)
+            })?;
+
+        Ok(())
+    }
 }
 
 impl AsRawFd for SPI {
@@ -224,17 +844,22 @@ impl fmt::Debug for SPI {
             .field("bits_per_word", &self.bits_per_word())
             .field("bit_order", &self.bit_order())
             .field("ss_polarity", &self.ss_polarity())
+            .field("mode_flags", &self.mode_flags())
             .finish()
     }
 }
 
 mod private {
-    use std::os::raw::{c_int, c_ulong};
+    use std::convert::TryFrom;
+
+    use crate::{Error, Result};
 
-    #[cfg(target_env = "gnu")]
-    type IoctlNumType = c_ulong;
-    #[cfg(target_env = "musl")]
-    type IoctlNumType = c_int;
+    /// Keeps `SpiWord` limited to the buffer types spidev understands
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
 
     /// Clock Phase
     pub const SPI_CPHA: u8 = 0x01;
@@ -268,7 +893,7 @@ mod private {
     /// Receive with 4 wires
     pub const SPI_RX_QUAD: u32 = 0x800;
 
-    const SPI_IOC_MAGIC: u8 = 'k' as u8;
+    const SPI_IOC_MAGIC: u8 = b'k';
     const SPI_IOC_NR_TRANSFER: u8 = 0;
     const SPI_IOC_NR_MODE: u8 = 1;
     const SPI_IOC_NR_LSB_FIRST: u8 = 2;
@@ -276,100 +901,6 @@ mod private {
     const SPI_IOC_NR_MAX_SPEED_HZ: u8 = 4;
     const SPI_IOC_NR_MODE32: u8 = 5;
 
-    const NONE: u8 = 0;
-    const READ: u8 = 2;
-    const WRITE: u8 = 1;
-    const SIZEBITS: u8 = 14;
-    const DIRBITS: u8 = 2;
-
-    const NRBITS: IoctlNumType = 8;
-    const TYPEBITS: IoctlNumType = 8;
-
-    const NRSHIFT: IoctlNumType = 0;
-    const TYPESHIFT: IoctlNumType = NRSHIFT + NRBITS as IoctlNumType;
-    const SIZESHIFT: IoctlNumType = TYPESHIFT + TYPEBITS as IoctlNumType;
-    const DIRSHIFT: IoctlNumType = SIZESHIFT + SIZEBITS as IoctlNumType;
-
-    const NRMASK: IoctlNumType = (1 << NRBITS) - 1;
-    const TYPEMASK: IoctlNumType = (1 << TYPEBITS) - 1;
-    const SIZEMASK: IoctlNumType = (1 << SIZEBITS) - 1;
-    const DIRMASK: IoctlNumType = (1 << DIRBITS) - 1;
-
-    macro_rules! ioc {
-        ($dir:expr, $ty:expr, $nr:expr, $sz:expr) => (
-            (($dir as IoctlNumType & DIRMASK) << DIRSHIFT) |
-            (($ty as IoctlNumType & TYPEMASK) << TYPESHIFT) |
-            (($nr as IoctlNumType & NRMASK) << NRSHIFT) |
-            (($sz as IoctlNumType & SIZEMASK) << SIZESHIFT))
-    }
-
-    // const SPI_IOC_RD_MODE: IoctlNumType = ioc!(READ, SPI_IOC_MAGIC, SPI_IOC_NR_MODE, mem::size_of::<u8>());
-
-    macro_rules! request_code_none {
-        ($ty:expr, $nr:expr) => (ioc!(NONE, $ty, $nr, 0))
-    }
-
-    macro_rules! request_code_read {
-        ($ty:expr, $nr:expr, $sz:expr) => (ioc!(READ, $ty, $nr, $sz))
-    }
-
-    macro_rules! request_code_write {
-        ($ty:expr, $nr:expr, $sz:expr) => (ioc!(WRITE, $ty, $nr, $sz))
-    }
-
-    macro_rules! request_code_readwrite {
-        ($ty:expr, $nr:expr, $sz:expr) => (ioc!(READ | WRITE, $ty, $nr, $sz))
-    }
-
-    macro_rules! ioctl_read {
-        ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
-            $(#[$attr])*
-            pub fn $name(fd: std::os::raw::c_int, data: *mut $ty) -> std::io::Result<std::os::raw::c_int> {
-                syscall!(ioctl(fd, request_code_read!($ioty, $nr, std::mem::size_of::<$ty>()) as IoctlNumType, data))
-            }
-        )
-    }
-
-    macro_rules! ioctl_write_ptr {
-        ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
-            $(#[$attr])*
-            pub fn $name(fd: std::os::raw::c_int, data: *const $ty) -> std::io::Result<std::os::raw::c_int> {
-                syscall!(ioctl(fd, request_code_write!($ioty, $nr, std::mem::size_of::<$ty>()) as IoctlNumType, data))
-            }
-        )
-    }
-
-    macro_rules! ioctl_read_buf {
-        ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
-            $(#[$attr])*
-            pub fn $name(fd: std::os::raw::c_int,
-                                data: &mut [$ty])
-                                -> std::io::Result<std::os::raw::c_int> {
-                syscall!(ioctl(fd, request_code_read!($ioty, $nr, data.len() * ::std::mem::size_of::<$ty>()) as IoctlNumType, data))
-            }
-        )
-    }
-
-    macro_rules! ioctl_write_buf {
-        ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
-            $(#[$attr])*
-            pub fn $name(fd: std::os::raw::c_int, data: &[$ty]) -> std::io::Result<std::os::raw::c_int> {
-                syscall!(ioctl(fd, request_code_write!($ioty, $nr, data.len() * ::std::mem::size_of::<$ty>()) as IoctlNumType, data))
-            }
-        )
-    }
-
-    macro_rules! ioctl_readwrite_buf {
-        ($(#[$attr:meta])* $name:ident, $ioty:expr, $nr:expr, $ty:ty) => (
-            $(#[$attr])*
-            pub fn $name(fd: std::os::raw::c_int,
-                                data: &mut [$ty])
-                                -> std::io::Result<std::os::raw::c_int> {
-                syscall!(ioctl(fd, request_code_readwrite!($ioty, $nr, data.len() * ::std::mem::size_of::<$ty>()) as IoctlNumType, data))
-            }
-        )
-    }
-
     ioctl_read!(get_mode_u8, SPI_IOC_MAGIC, SPI_IOC_NR_MODE, u8);
     ioctl_write_ptr!(set_mode_u8, SPI_IOC_MAGIC, SPI_IOC_NR_MODE, u8);
     ioctl_read!(get_mode_u32, SPI_IOC_MAGIC, SPI_IOC_NR_MODE32, u32);
@@ -397,42 +928,109 @@ mod private {
         pub delay_usecs: u16,
         pub bits_per_word: u8,
         pub cs_change: u8,
-        pub pad: u32,
+        /// Number of wires to send on, 0 for the default of 1
+        pub tx_nbits: u8,
+        /// Number of wires to receive on, 0 for the default of 1
+        pub rx_nbits: u8,
+        pub word_delay_usecs: u8,
+        pad: u8,
 
         tx_buf_ref: std::marker::PhantomData<&'a [u8]>,
         rx_buf_ref: std::marker::PhantomData<&'b mut [u8]>,
     }
 
     impl<'a, 'b> spi_ioc_transfer<'a, 'b> {
-        pub fn read(buff: &'b mut [u8]) -> Self {
-            spi_ioc_transfer {
+        pub fn read(buff: &'b mut [u8]) -> Result<Self> {
+            Ok(spi_ioc_transfer {
                 rx_buf: buff.as_ptr() as *const () as usize as u64,
-                len: buff.len() as u32,
+                len: transfer_len(buff.len())?,
                 ..Default::default()
-            }
+            })
         }
 
-        pub fn write(buff: &'a [u8]) -> Self {
-            spi_ioc_transfer {
+        pub fn write(buff: &'a [u8]) -> Result<Self> {
+            Ok(spi_ioc_transfer {
                 tx_buf: buff.as_ptr() as *const () as usize as u64,
-                len: buff.len() as u32,
+                len: transfer_len(buff.len())?,
                 ..Default::default()
-            }
+            })
         }
 
         /// The `tx_buf` and `rx_buf` must be the same length.
-        pub fn read_write(tx_buf: &'a [u8], rx_buf: &'b mut [u8]) -> Self {
-            assert_eq!(tx_buf.len(), rx_buf.len());
-            spi_ioc_transfer {
+        pub fn read_write(tx_buf: &'a [u8], rx_buf: &'b mut [u8]) -> Result<Self> {
+            if tx_buf.len() != rx_buf.len() {
+                return Err(Error::InvalidArgument(format!(
+                    "SPI transfer buffers differ in length, {} bytes to send and {} to receive",
+                    tx_buf.len(), rx_buf.len()
+                )))
+            }
+
+            Ok(spi_ioc_transfer {
                 rx_buf: rx_buf.as_ptr() as *const () as usize as u64,
                 tx_buf: tx_buf.as_ptr() as *const () as usize as u64,
-                len: tx_buf.len() as u32,
+                len: transfer_len(tx_buf.len())?,
                 ..Default::default()
-            }
+            })
         }
     }
 
+    fn transfer_len(len: usize) -> Result<u32> {
+        u32::try_from(len)
+            .map_err(|_| Error::InvalidArgument(format!("SPI transfer of {} bytes is too long", len)))
+    }
+
+    // The ioctl numbers encode the struct size, so make sure it matches the uAPI
+    const _: () = assert!(std::mem::size_of::<spi_ioc_transfer>() == 32);
 
     ioctl_write_ptr!(spidev_transfer, SPI_IOC_MAGIC, SPI_IOC_NR_TRANSFER, spi_ioc_transfer);
     ioctl_write_buf!(spidev_transfer_buf, SPI_IOC_MAGIC, SPI_IOC_NR_TRANSFER, spi_ioc_transfer);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_argument<T: fmt::Debug>(result: Result<T>) -> String {
+        match result {
+            Err(Error::InvalidArgument(msg)) => msg,
+            other => panic!("expected InvalidArgument, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn word_types_name_the_buffer_needed() {
+        assert!(WordType::of::<u8>().check(0).is_ok());
+        assert!(WordType::of::<u16>().check(9).is_ok());
+        assert_eq!(invalid_argument(WordType::of::<u8>().check(12)), "12-bit SPI words need a buffer of u16, not u8");
+        assert_eq!(invalid_argument(WordType::of::<u32>().check(0)), "8-bit SPI words need a buffer of u8, not u32");
+        assert_eq!(invalid_argument(WordType::of::<u16>().check(24)), "24-bit SPI words need a buffer of u32, not u16");
+    }
+
+    #[test]
+    fn words_are_passed_in_native_byte_order() {
+        let words = [0x0102u16, 0x0304];
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        assert_eq!(word_bytes(&words), &bytes[..]);
+
+        let mut words = [0u16; 2];
+        word_bytes_mut(&mut words).copy_from_slice(&bytes);
+        assert_eq!(words, [0x0102, 0x0304]);
+
+        assert_eq!(word_bytes(&[0x01020304u32]), &0x01020304u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn transactions_check_word_size_overrides_against_the_buffer() {
+        let tx = [0x1ffu16; 2];
+        let mut rx = [0u8; 2];
+
+        let transaction = SpiTransaction::new().write(&tx).bits_per_word(9).read(&mut rx).bits_per_word(8);
+        assert!(transaction.error.is_none());
+        assert_eq!(transaction.words, [WordType::of::<u16>(), WordType::of::<u8>()]);
+        assert_eq!(transaction.transfers[0].bits_per_word, 9);
+
+        let transaction = SpiTransaction::new().write(&tx).bits_per_word(8);
+        assert_eq!(invalid_argument(transaction.error.map_or(Ok(()), Err)), "8-bit SPI words need a buffer of u8, not u16");
+        assert_eq!(transaction.transfers[0].bits_per_word, 0);
+    }
+}
@@ -0,0 +1,96 @@
+// A fake sysfs and /dev tree shared by the integration tests.
+//
+// The root set with `sys::set_root` is global to the process, so tests
+// holding a `FakeRoot` run one at a time, each in a fresh directory under the
+// temp dir that is removed again when the `FakeRoot` is dropped.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use periphery::sys;
+use periphery::sys::gpio::Pin;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+pub struct FakeRoot {
+    path: PathBuf,
+    _lock: MutexGuard<'static, ()>
+}
+
+impl FakeRoot {
+    /// Create an empty root named after `name` and make it the `sys::root`
+    pub fn new(name: &str) -> FakeRoot {
+        // A test that panicked still removed its tree, so the poison is harmless
+        let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let path = std::env::temp_dir().join(format!("periphery-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        sys::set_root(&path);
+
+        FakeRoot { path, _lock: lock }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The absolute path of `path` inside the root
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+
+    /// Create the directory `path` and its parents, returns its absolute path
+    pub fn create_dir<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = self.join(path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    /// Write `contents` to the file `path`, creating its parent directories
+    pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&self, path: P, contents: C) -> PathBuf {
+        let path = self.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Create the symlink `path` pointing at `target`
+    pub fn symlink<P: AsRef<Path>>(&self, target: &str, path: P) {
+        symlink(target, self.join(path)).unwrap();
+    }
+
+    /// Create an exported `gpio{num}` set up as an input reading low, returns
+    /// the pin and its directory
+    pub fn gpio_pin(&self, num: usize) -> (Pin, PathBuf) {
+        let dir = format!("sys/class/gpio/gpio{}", num);
+        self.write(format!("{}/direction", dir), "in\n");
+        self.write(format!("{}/value", dir), "0\n");
+
+        (Pin::new(num), self.join(dir))
+    }
+
+    /// Create `pwmchip{chip}` with a single exported `pwm0` holding
+    /// `attributes`, returns the channel directory
+    pub fn pwm_channel(&self, chip: u32, attributes: &[(&str, &[u8])]) -> PathBuf {
+        let chip_dir = format!("sys/class/pwm/pwmchip{}", chip);
+        let pwm_dir = self.create_dir(format!("{}/pwm0", chip_dir));
+
+        self.write(format!("{}/npwm", chip_dir), "1\n");
+        for (name, contents) in attributes {
+            fs::write(pwm_dir.join(name), contents).unwrap();
+        }
+
+        pwm_dir
+    }
+}
+
+impl Drop for FakeRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,24 @@
+// Runs the sysfs GPIO interface against a fake tree under the temp dir.
+
+use std::time::Duration;
+
+use periphery::sys::gpio::Edge;
+use periphery::Error;
+
+mod common;
+
+use common::FakeRoot;
+
+#[test]
+fn events_require_a_configured_edge() {
+    let root = FakeRoot::new("gpio");
+    let (pin, dir) = root.gpio_pin(20);
+    root.write(dir.join("edge"), "none\n");
+
+    assert!(matches!(pin.events(), Err(Error::InvalidArgument(_))));
+    assert!(matches!(pin.poll(Some(Duration::ZERO)), Err(Error::InvalidArgument(_))));
+    assert!(matches!(pin.wait_for_edge(), Err(Error::InvalidArgument(_))));
+
+    pin.set_edge(Edge::Rising).unwrap();
+    assert!(pin.events().is_ok());
+}
@@ -0,0 +1,67 @@
+// Discovers GPIO chips and lines in a fake sysfs tree under the temp dir.
+
+use periphery::sys::{gpio::Pin, gpiochip::GpioChip};
+use periphery::Error;
+
+mod common;
+
+use common::FakeRoot;
+
+/// Create `/sys/class/gpio/gpiochip{base}`, optionally linked to the device `device`
+fn fake_chip(root: &FakeRoot, base: u32, label: &str, ngpio: u32, device: Option<&str>) {
+    let dir = format!("sys/class/gpio/gpiochip{}", base);
+    root.write(format!("{}/label", dir), format!("{}\n", label));
+    root.write(format!("{}/base", dir), format!("{}\n", base));
+    root.write(format!("{}/ngpio", dir), format!("{}\n", ngpio));
+
+    if let Some(device) = device {
+        root.create_dir(format!("sys/devices/platform/soc/{}", device));
+        root.symlink(&format!("../../../devices/platform/soc/{}", device), format!("{}/device", dir));
+    }
+}
+
+#[test]
+fn finds_lines_by_chip_label_and_offset() {
+    let root = FakeRoot::new("gpio-discovery");
+    fake_chip(&root, 512, "pinctrl-bcm2711", 58, Some("fe200000.gpio/gpiochip0"));
+    fake_chip(&root, 570, "raspberrypi-exp-gpio", 8, None);
+
+    let chips = GpioChip::list().unwrap();
+    assert_eq!(chips.len(), 2);
+    assert_eq!(chips[0].name.as_deref(), Some("gpiochip0"));
+    assert_eq!(chips[0].label, "pinctrl-bcm2711");
+    assert_eq!((chips[0].base, chips[0].ngpio), (Some(512), 58));
+    assert_eq!(chips[1].name, None);
+    assert_eq!((chips[1].base, chips[1].ngpio), (Some(570), 8));
+
+    let line = GpioChip::find_line("pinctrl-bcm2711", 17).unwrap().unwrap();
+    assert_eq!(line.offset, 17);
+    assert_eq!(line.pin(), Some(Pin::new(529)));
+    assert_eq!(GpioChip::find_line("gpiochip0", 17).unwrap(), Some(line));
+
+    assert!(GpioChip::find_line("pinctrl-bcm2711", 58).unwrap().is_none());
+    assert!(GpioChip::find_line("pinctrl-bcm2835", 0).unwrap().is_none());
+
+    let expander = GpioChip::find_line("raspberrypi-exp-gpio", 2).unwrap().unwrap();
+    assert_eq!(expander.pin(), Some(Pin::new(572)));
+    assert!(matches!(expander.open_chip(), Err(Error::UnsupportedFeature(_))));
+}
+
+#[test]
+fn skips_chips_that_cannot_be_queried() {
+    let root = FakeRoot::new("gpio-discovery");
+    fake_chip(&root, 600, "pinctrl-rp1", 54, Some("1f000d0000.gpio/gpiochip4"));
+
+    // Not character devices, so the chip and line info ioctls fail
+    root.write("dev/gpiochip4", b"");
+    root.write("dev/gpiochip7", b"");
+    root.write("sys/class/gpio/gpiochip700/label", "broken\n");
+    root.write("sys/class/gpio/gpiochip700/ngpio", "many\n");
+
+    let chips = GpioChip::list().unwrap();
+    assert_eq!(chips.len(), 1);
+    assert_eq!(chips[0].label, "pinctrl-rp1");
+
+    // The lines of gpiochip4 cannot be queried, which ends its search only
+    assert!(GpioChip::find_line_by_name("GPIO17").unwrap().is_none());
+}
@@ -0,0 +1,169 @@
+// Runs the GPIO character device backend against a gpio-sim chip.
+//
+// The tests are skipped unless a simulated chip with at least two lines is
+// live, and they need root to write the simulator's attributes. To create one:
+//
+//     modprobe gpio-sim
+//     mkdir -p /sys/kernel/config/gpio-sim/periphery/bank0
+//     echo 2 > /sys/kernel/config/gpio-sim/periphery/bank0/num_lines
+//     echo 1 > /sys/kernel/config/gpio-sim/periphery/live
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use periphery::sys;
+use periphery::sys::gpio::{Direction, Edge, Value};
+use periphery::sys::gpiochip::{GpioChip, LineConfig};
+
+/// The simulator's lines are shared by all tests, so they run one at a time
+static LOCK: Mutex<()> = Mutex::new(());
+
+struct Sim {
+    chip: GpioChip,
+    /// The `gpiochipN` directory holding the `sim_gpioM` attributes
+    dir: PathBuf,
+    _lock: MutexGuard<'static, ()>
+}
+
+impl Sim {
+    /// The first live gpio-sim chip with at least two lines
+    fn find() -> Option<Sim> {
+        let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let devices = fs::read_dir(sys::root().join("sys/bus/platform/devices")).ok()?;
+
+        for device in devices.flatten() {
+            if !device.file_name().to_string_lossy().starts_with("gpio-sim") {
+                continue
+            }
+
+            for entry in fs::read_dir(device.path()).ok()?.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+
+                if name.starts_with("gpiochip") && entry.path().join("sim_gpio1").exists() {
+                    let chip = GpioChip::from_path(sys::root().join("dev").join(&name)).ok()?;
+
+                    return Some(Sim { chip, dir: entry.path(), _lock: lock })
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Pull the input `offset` up or down from the outside
+    fn pull(&self, offset: u32, value: Value) {
+        let pull = match value {
+            Value::High => "pull-up",
+            Value::Low => "pull-down"
+        };
+
+        fs::write(self.dir.join(format!("sim_gpio{}/pull", offset)), pull).unwrap();
+    }
+
+    /// The level the output `offset` drives
+    fn value(&self, offset: u32) -> Value {
+        match fs::read_to_string(self.dir.join(format!("sim_gpio{}/value", offset))).unwrap().trim() {
+            "0" => Value::Low,
+            "1" => Value::High,
+            other => panic!("unexpected simulated value {:?}", other)
+        }
+    }
+}
+
+macro_rules! sim_or_skip {
+    () => {
+        match Sim::find() {
+            Some(sim) => sim,
+            None => {
+                eprintln!("no live gpio-sim chip with two lines, skipping");
+                return
+            }
+        }
+    };
+}
+
+#[test]
+fn line_reads_inputs_and_drives_outputs() {
+    let sim = sim_or_skip!();
+
+    let input = sim.chip.request_line(0, "periphery-test", &LineConfig::default()).unwrap();
+    assert_eq!(input.offset(), 0);
+
+    sim.pull(0, Value::High);
+    assert_eq!(input.value().unwrap(), Value::High);
+    sim.pull(0, Value::Low);
+    assert_eq!(input.value().unwrap(), Value::Low);
+
+    let config = LineConfig { direction: Direction::Low, ..Default::default() };
+    let output = sim.chip.request_line(1, "periphery-test", &config).unwrap();
+    assert_eq!(sim.value(1), Value::Low);
+
+    output.set_value(Value::High).unwrap();
+    assert_eq!(sim.value(1), Value::High);
+    output.set_value(Value::Low).unwrap();
+    assert_eq!(sim.value(1), Value::Low);
+}
+
+#[test]
+fn active_low_lines_are_inverted() {
+    let sim = sim_or_skip!();
+
+    let config = LineConfig { active_low: true, ..Default::default() };
+    let input = sim.chip.request_line(0, "periphery-test", &config).unwrap();
+
+    sim.pull(0, Value::High);
+    assert_eq!(input.value().unwrap(), Value::Low);
+    sim.pull(0, Value::Low);
+    assert_eq!(input.value().unwrap(), Value::High);
+
+    let config = LineConfig { direction: Direction::Out, active_low: true, ..Default::default() };
+    let output = sim.chip.request_line(1, "periphery-test", &config).unwrap();
+
+    output.set_value(Value::High).unwrap();
+    assert_eq!(sim.value(1), Value::Low);
+    output.set_value(Value::Low).unwrap();
+    assert_eq!(sim.value(1), Value::High);
+}
+
+#[test]
+fn lines_are_written_and_read_together() {
+    let sim = sim_or_skip!();
+
+    let config = LineConfig { direction: Direction::Low, ..Default::default() };
+    let lines = sim.chip.request_lines(&[0, 1], "periphery-test", &config).unwrap();
+    assert_eq!(lines.offsets(), &[0, 1]);
+
+    lines.set_values(0b10).unwrap();
+    assert_eq!(sim.value(0), Value::Low);
+    assert_eq!(sim.value(1), Value::High);
+    assert_eq!(lines.values().unwrap(), 0b10);
+
+    lines.set_values_masked(0b01, 0b01).unwrap();
+    assert_eq!(sim.value(0), Value::High);
+    assert_eq!(sim.value(1), Value::High);
+    assert_eq!(lines.values().unwrap(), 0b11);
+}
+
+#[test]
+fn edges_are_reported_as_events() {
+    let sim = sim_or_skip!();
+    sim.pull(0, Value::Low);
+
+    let config = LineConfig { edge: Edge::Both, ..Default::default() };
+    let line = sim.chip.request_line(0, "periphery-test", &config).unwrap();
+    let timeout = Some(Duration::from_secs(1));
+
+    assert!(line.poll(Some(Duration::ZERO)).unwrap().is_none());
+
+    sim.pull(0, Value::High);
+    let rising = line.poll(timeout).unwrap().expect("no rising edge");
+    assert_eq!(rising.edge, Edge::Rising);
+
+    sim.pull(0, Value::Low);
+    let falling = line.events().next().unwrap().unwrap();
+    assert_eq!(falling.edge, Edge::Falling);
+    assert!(falling.timestamp >= rising.timestamp);
+}
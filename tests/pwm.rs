@@ -0,0 +1,262 @@
+// Runs the sysfs PWM interface against a fake tree under the temp dir.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use periphery::sys::pwm::{ExportedPwm, Polarity, Pwm, PwmBackend, PwmCapture, PwmChip, PwmState};
+use periphery::Error;
+
+mod common;
+
+use common::FakeRoot;
+
+fn assert_unexpected<T: std::fmt::Debug>(result: periphery::Result<T>, expected_path: PathBuf, expected: &str) {
+    match result {
+        Err(Error::UnexpectedSysfsContent { path, contents }) => {
+            assert_eq!(path, expected_path);
+            assert_eq!(contents, expected);
+        }
+        other => panic!("expected UnexpectedSysfsContent, got {:?}", other),
+    }
+}
+
+#[test]
+fn parses_well_formed_attributes() {
+    let root = FakeRoot::new("pwm");
+    root.pwm_channel(0, &[
+        ("enable", b"1\n"),
+        ("period", b"20000000\n"),
+        ("duty_cycle", b"1500000\n"),
+    ]);
+    root.write("sys/class/pwm/pwmchip0/npwm", "2\n");
+
+    let pwm = Pwm::new(0, 0).unwrap();
+
+    assert!(pwm.enabled().unwrap());
+    assert_eq!(pwm.period_ns().unwrap(), 20_000_000);
+    assert_eq!(pwm.duty_cycle_ns().unwrap(), 1_500_000);
+    assert_eq!(PwmChip::new(0).unwrap().count().unwrap(), 2);
+}
+
+#[test]
+fn enabled_rejects_values_other_than_0_or_1() {
+    let root = FakeRoot::new("pwm");
+    for (i, contents) in ["2\n", "-1\n", "on\n", "\n", ""].iter().enumerate() {
+        let chip = 10 + i as u32;
+        let dir = root.pwm_channel(chip, &[("enable", contents.as_bytes())]);
+
+        let pwm = Pwm::new(chip, 0).unwrap();
+
+        assert_unexpected(pwm.enabled(), dir.join("enable"), contents);
+    }
+}
+
+#[test]
+fn numeric_attributes_reject_malformed_contents() {
+    let root = FakeRoot::new("pwm");
+    let malformed: [&[u8]; 7] = [
+        b"",
+        b"\n",
+        b"abc\n",
+        b"-5\n",
+        b"1.5\n",
+        b"99999999999999999999\n",
+        b"12 34\n",
+    ];
+
+    for (i, contents) in malformed.iter().enumerate() {
+        let chip = 20 + i as u32;
+        let dir = root.pwm_channel(chip, &[("period", contents), ("duty_cycle", contents)]);
+
+        let pwm = Pwm::new(chip, 0).unwrap();
+        let expected = String::from_utf8_lossy(contents);
+
+        assert_unexpected(pwm.period_ns(), dir.join("period"), &expected);
+        assert_unexpected(pwm.duty_cycle_ns(), dir.join("duty_cycle"), &expected);
+    }
+}
+
+#[test]
+fn invalid_utf8_is_reported_as_unexpected_content() {
+    let root = FakeRoot::new("pwm");
+    let dir = root.pwm_channel(30, &[("period", b"\xff\xfe\n"), ("enable", b"\xff")]);
+
+    let pwm = Pwm::new(30, 0).unwrap();
+
+    assert_unexpected(pwm.period_ns(), dir.join("period"), "\u{fffd}\u{fffd}\n");
+    assert_unexpected(pwm.enabled(), dir.join("enable"), "\u{fffd}");
+}
+
+#[test]
+fn count_rejects_malformed_npwm() {
+    let root = FakeRoot::new("pwm");
+    let dir = root.pwm_channel(31, &[]);
+    root.write("sys/class/pwm/pwmchip31/npwm", "two\n");
+
+    let chip = PwmChip::new(31).unwrap();
+
+    assert_unexpected(chip.count(), dir.parent().unwrap().join("npwm"), "two\n");
+}
+
+#[test]
+fn missing_channel_is_not_exported() {
+    let root = FakeRoot::new("pwm");
+    let dir = root.pwm_channel(32, &[]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let pwm = Pwm::new(32, 0).unwrap();
+
+    match pwm.period_ns() {
+        Err(Error::NotExported(path)) => assert_eq!(path, dir.join("period")),
+        other => panic!("expected NotExported, got {:?}", other),
+    }
+}
+
+#[test]
+fn polarity_is_parsed_and_rejects_unknown_values() {
+    let root = FakeRoot::new("pwm");
+    let dir = root.pwm_channel(40, &[("polarity", b"inversed\n")]);
+
+    let pwm = Pwm::new(40, 0).unwrap();
+    assert_eq!(pwm.polarity().unwrap(), Polarity::Inverse);
+
+    fs::write(dir.join("polarity"), b"reversed\n").unwrap();
+    assert_unexpected(pwm.polarity(), dir.join("polarity"), "reversed\n");
+}
+
+#[test]
+fn apply_writes_the_complete_state() {
+    let root = FakeRoot::new("pwm");
+    root.pwm_channel(41, &[
+        ("enable", b"1\n"),
+        ("period", b"1000\n"),
+        ("duty_cycle", b"500\n"),
+        ("polarity", b"normal\n"),
+    ]);
+
+    let pwm = Pwm::new(41, 0).unwrap();
+    let state = PwmState {
+        period_ns: 400,
+        duty_cycle_ns: 100,
+        polarity: Polarity::Inverse,
+        enabled: true
+    };
+
+    pwm.apply(&state).unwrap();
+    assert_eq!(pwm.state().unwrap(), state);
+
+    let invalid = PwmState { duty_cycle_ns: 500, ..state };
+    assert!(matches!(pwm.apply(&invalid), Err(Error::InvalidArgument(_))));
+}
+
+#[test]
+fn periods_beyond_u32_nanoseconds_are_supported() {
+    let root = FakeRoot::new("pwm");
+    root.pwm_channel(42, &[("period", b"10000000000\n"), ("duty_cycle", b"0\n")]);
+
+    let pwm = Pwm::new(42, 0).unwrap();
+    assert_eq!(pwm.period_ns().unwrap(), 10_000_000_000);
+
+    pwm.set_period_ns(20_000_000_000).unwrap();
+    assert_eq!(pwm.period_ns().unwrap(), 20_000_000_000);
+    assert_eq!(pwm.frequency_hz().unwrap(), 0.05);
+}
+
+#[test]
+fn frequency_changes_keep_the_duty_ratio() {
+    let root = FakeRoot::new("pwm");
+    root.pwm_channel(43, &[
+        ("enable", b"0\n"),
+        ("period", b"0\n"),
+        ("duty_cycle", b"0\n"),
+        ("polarity", b"normal\n"),
+    ]);
+
+    let pwm = Pwm::new(43, 0).unwrap();
+    assert!(matches!(pwm.set_duty_cycle(0.5), Err(Error::InvalidArgument(_))));
+
+    pwm.set_frequency_hz(1000.0).unwrap();
+    pwm.set_duty_cycle(0.25).unwrap();
+    assert_eq!(pwm.period_ns().unwrap(), 1_000_000);
+    assert_eq!(pwm.duty_cycle_ns().unwrap(), 250_000);
+
+    pwm.set_frequency_hz(50.0).unwrap();
+    assert_eq!(pwm.period_ns().unwrap(), 20_000_000);
+    assert_eq!(pwm.duty_cycle_ns().unwrap(), 5_000_000);
+    assert_eq!(pwm.duty_cycle().unwrap(), 0.25);
+
+    assert!(matches!(pwm.set_duty_cycle(1.5), Err(Error::InvalidArgument(_))));
+    assert!(matches!(pwm.set_frequency_hz(0.0), Err(Error::InvalidArgument(_))));
+    assert!(matches!(pwm.set_frequency_hz(f64::NAN), Err(Error::InvalidArgument(_))));
+}
+
+#[test]
+fn uses_sysfs_unless_the_character_device_is_requested() {
+    let root = FakeRoot::new("pwm");
+    root.pwm_channel(44, &[("enable", b"1\n")]);
+
+    let pwm = Pwm::new(44, 0).unwrap();
+    assert_eq!(pwm.backend(), PwmBackend::Sysfs);
+    assert!(pwm.enabled().unwrap());
+
+    assert!(Pwm::with_backend(44, 0, PwmBackend::Cdev).is_err());
+
+    // A file that is not a PWM chip fails the waveform ioctls
+    root.write("dev/pwmchip44", b"");
+    assert_eq!(Pwm::new(44, 0).unwrap().backend(), PwmBackend::Sysfs);
+    assert!(matches!(
+        Pwm::with_backend(44, 0, PwmBackend::Cdev),
+        Err(Error::UnsupportedFeature(_))
+    ));
+
+    let pwm = Pwm::with_backend(44, 0, PwmBackend::Auto).unwrap();
+    assert_eq!(pwm.backend(), PwmBackend::Sysfs);
+    assert!(pwm.enabled().unwrap());
+
+    let pwm = Pwm::with_backend(44, 0, PwmBackend::Sysfs).unwrap();
+    assert_eq!(pwm.backend(), PwmBackend::Sysfs);
+}
+
+#[test]
+fn capture_parses_period_and_duty_cycle() {
+    let root = FakeRoot::new("pwm");
+    let dir = root.pwm_channel(45, &[("capture", b"20000000 1500000\n")]);
+
+    let pwm = Pwm::new(45, 0).unwrap();
+    let capture = pwm.capture(Duration::from_secs(1)).unwrap();
+    assert_eq!(capture, PwmCapture { period_ns: 20_000_000, duty_cycle_ns: 1_500_000 });
+    assert_eq!(capture.frequency_hz(), 50.0);
+    assert_eq!(capture.duty_cycle(), 0.075);
+
+    for contents in ["20000000\n", "20000000 x\n", "1 2 3\n"] {
+        fs::write(dir.join("capture"), contents).unwrap();
+        assert_unexpected(pwm.capture(Duration::from_secs(1)), dir.join("capture"), contents);
+    }
+}
+
+#[test]
+fn guards_leave_channels_they_did_not_export() {
+    let root = FakeRoot::new("pwm");
+    let dir = root.pwm_channel(46, &[
+        ("enable", b"1\n"),
+        ("period", b"1000\n"),
+        ("duty_cycle", b"500\n"),
+    ]);
+
+    let guard = ExportedPwm::new(Pwm::new(46, 0).unwrap()).unwrap();
+    assert!(guard.is_exported());
+    drop(guard);
+
+    assert_eq!(fs::read_to_string(dir.join("enable")).unwrap(), "1\n");
+    assert!(!root.join("sys/class/pwm/pwmchip46/export").exists());
+    assert!(!root.join("sys/class/pwm/pwmchip46/unexport").exists());
+
+    // Disabling can still be asked for explicitly
+    let mut guard = ExportedPwm::new(Pwm::new(46, 0).unwrap()).unwrap();
+    guard.set_disable_on_drop(true);
+    drop(guard);
+
+    assert_eq!(fs::read_to_string(dir.join("enable")).unwrap(), "0");
+    assert!(!root.join("sys/class/pwm/pwmchip46/unexport").exists());
+}
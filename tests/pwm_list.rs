@@ -0,0 +1,62 @@
+// Enumerates PWM chips in a fake sysfs tree under the temp dir.
+
+use std::fs;
+use std::path::PathBuf;
+
+use periphery::sys::pwm::PwmChip;
+
+mod common;
+
+use common::FakeRoot;
+
+/// Create `pwmchip{chip}` with `npwm` channels, returns the chip directory
+fn fake_chip(root: &FakeRoot, chip: u32, npwm: &str) -> PathBuf {
+    let chip_dir = format!("sys/class/pwm/pwmchip{}", chip);
+    root.write(format!("{}/npwm", chip_dir), npwm);
+
+    root.join(chip_dir)
+}
+
+#[test]
+fn lists_chips_with_their_device_and_device_tree_node() {
+    let root = FakeRoot::new("pwm-list");
+    fake_chip(&root, 0, "2\n");
+    let device = root.create_dir("sys/devices/platform/7e20c000.pwm");
+    root.create_dir("sys/bus/platform/drivers/pwm-bcm2835");
+    root.create_dir("sys/firmware/devicetree/base/soc/pwm@7e20c000");
+
+    root.symlink("../../../devices/platform/7e20c000.pwm", "sys/class/pwm/pwmchip0/device");
+    root.symlink("../../../bus/platform/drivers/pwm-bcm2835", "sys/devices/platform/7e20c000.pwm/driver");
+    root.symlink("../../../firmware/devicetree/base/soc/pwm@7e20c000", "sys/devices/platform/7e20c000.pwm/of_node");
+    fake_chip(&root, 1, "1\n");
+
+    let chips = PwmChip::list().unwrap();
+    let info = chips.iter().find(|info| info.number == 0).unwrap();
+    assert_eq!(info.npwm, 2);
+    assert_eq!(info.device.as_deref(), Some("7e20c000.pwm"));
+    assert_eq!(info.driver.as_deref(), Some("pwm-bcm2835"));
+    assert_eq!(info.device_path, Some(fs::canonicalize(&device).unwrap()));
+    assert_eq!(info.of_node, Some(PathBuf::from("/soc/pwm@7e20c000")));
+
+    let bare = chips.iter().find(|info| info.number == 1).unwrap();
+    assert_eq!((bare.device.clone(), bare.of_node.clone()), (None, None));
+
+    assert_eq!(PwmChip::find("7e20c000.pwm").unwrap().unwrap().number, 0);
+    assert_eq!(PwmChip::find("/soc/pwm@7e20c000").unwrap().unwrap().number, 0);
+    assert_eq!(PwmChip::find("pwm@7e20c000").unwrap().unwrap().number, 0);
+    assert!(PwmChip::find("pwm@0").unwrap().is_none());
+}
+
+#[test]
+fn skips_chips_with_unreadable_npwm() {
+    let root = FakeRoot::new("pwm-list");
+    fake_chip(&root, 0, "1\n");
+    fake_chip(&root, 1, "two\n");
+    fs::remove_file(fake_chip(&root, 2, "1\n").join("npwm")).unwrap();
+    fake_chip(&root, 3, "4\n");
+
+    let chips = PwmChip::list().unwrap();
+    let numbers: Vec<u32> = chips.iter().map(|info| info.number).collect();
+    assert_eq!(numbers, [0, 3]);
+    assert!(PwmChip::find("pwm@0").unwrap().is_none());
+}
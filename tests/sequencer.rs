@@ -0,0 +1,131 @@
+// Plays sequences on a PWM output that records every duty cycle written.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use periphery::sequencer::{Curve, Outcome, Sequence};
+use periphery::sys::pwm::PwmOutput;
+use periphery::{Error, Result};
+
+#[derive(Default)]
+struct Recorder {
+    duty_cycles: Mutex<Vec<f64>>
+}
+
+impl Recorder {
+    fn history(&self) -> Vec<f64> {
+        self.duty_cycles.lock().unwrap().clone()
+    }
+}
+
+impl PwmOutput for Recorder {
+    fn enable(&self, _enable: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn enabled(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn period_ns(&self) -> Result<u64> {
+        Ok(1_000_000)
+    }
+
+    fn set_period_ns(&self, _period_ns: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn duty_cycle_ns(&self) -> Result<u64> {
+        Ok((self.duty_cycle()? * 1e6) as u64)
+    }
+
+    fn set_duty_cycle_ns(&self, duty_cycle_ns: u64) -> Result<()> {
+        self.set_duty_cycle(duty_cycle_ns as f64 / 1e6)
+    }
+
+    fn frequency_hz(&self) -> Result<f64> {
+        Ok(1000.0)
+    }
+
+    fn set_frequency_hz(&self, _frequency_hz: f64) -> Result<()> {
+        Ok(())
+    }
+
+    fn duty_cycle(&self) -> Result<f64> {
+        Ok(self.duty_cycles.lock().unwrap().last().copied().unwrap_or(0.0))
+    }
+
+    fn set_duty_cycle(&self, duty_cycle: f64) -> Result<()> {
+        if !(0.0..=1.0).contains(&duty_cycle) {
+            return Err(Error::InvalidArgument(format!("{}", duty_cycle)))
+        }
+
+        self.duty_cycles.lock().unwrap().push(duty_cycle);
+        Ok(())
+    }
+}
+
+#[test]
+fn plays_duty_cycle_sequences_in_order() {
+    let output = Arc::new(Recorder::default());
+    let sequencer = Sequence::new()
+        .duty_cycles(vec![0.1, 0.5, 0.9], Duration::from_millis(1))
+        .set(0.0)
+        .start(output.clone())
+        .unwrap();
+
+    assert_eq!(sequencer.wait().unwrap(), Outcome::Finished);
+    assert_eq!(output.history(), vec![0.1, 0.5, 0.9, 0.0]);
+}
+
+#[test]
+fn ramps_end_exactly_at_the_target() {
+    for curve in [Curve::Linear, Curve::Exponential] {
+        let output = Arc::new(Recorder::default());
+        let sequencer = Sequence::new()
+            .ramp(1.0, Duration::from_millis(30), curve)
+            .ramp(0.25, Duration::from_millis(30), curve)
+            .interval(Duration::from_millis(2))
+            .start(output.clone())
+            .unwrap();
+
+        assert!(sequencer.wait_timeout(Duration::from_secs(5)));
+        assert!(sequencer.is_finished());
+        assert_eq!(sequencer.wait().unwrap(), Outcome::Finished);
+
+        let history = output.history();
+        assert!(history.contains(&1.0));
+        assert_eq!(*history.last().unwrap(), 0.25);
+    }
+}
+
+#[test]
+fn repeating_sequences_run_until_cancelled() {
+    let output = Arc::new(Recorder::default());
+    let sequencer = Sequence::new()
+        .duty_cycles(vec![0.0, 1.0], Duration::from_millis(1))
+        .repeat(true)
+        .start(output.clone())
+        .unwrap();
+
+    // Wait for the sequence to come round again
+    let start = Instant::now();
+    while output.history().len() < 4 {
+        assert!(start.elapsed() < Duration::from_secs(5), "sequence did not repeat");
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    sequencer.cancel();
+    assert_eq!(sequencer.wait().unwrap(), Outcome::Cancelled);
+    assert_eq!(output.history()[..4], [0.0, 1.0, 0.0, 1.0]);
+}
+
+#[test]
+fn rejects_invalid_sequences() {
+    let output = Arc::new(Recorder::default());
+
+    assert!(matches!(Sequence::new().set(1.5).start(output.clone()), Err(Error::InvalidArgument(_))));
+    assert!(matches!(Sequence::new().set(0.5).repeat(true).start(output.clone()), Err(Error::InvalidArgument(_))));
+    assert!(matches!(Sequence::new().interval(Duration::ZERO).start(output), Err(Error::InvalidArgument(_))));
+}
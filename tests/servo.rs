@@ -0,0 +1,92 @@
+// Runs the servo and motor helpers against a fake sysfs tree under the temp dir.
+
+use std::fs;
+
+use periphery::motor::Motor;
+use periphery::servo::{Servo, ServoConfig};
+use periphery::sys::{gpio::Value, pwm::Pwm};
+use periphery::Error;
+
+mod common;
+
+use common::FakeRoot;
+
+/// The attributes of a disabled channel
+const DISABLED: &[(&str, &[u8])] = &[
+    ("enable", b"0
+"),
+    ("period", b"0
+"),
+    ("duty_cycle", b"0
+"),
+    ("polarity", b"normal
+"),
+];
+
+#[test]
+fn servo_maps_and_clamps_angles() {
+    let root = FakeRoot::new("servo");
+    root.pwm_channel(0, DISABLED);
+    let pwm = Pwm::new(0, 0).unwrap();
+    let servo = Servo::new(pwm, ServoConfig::default()).unwrap();
+
+    servo.set_angle(90.0).unwrap();
+    assert_eq!(servo.pulse_ns().unwrap(), 1_500_000);
+    assert_eq!(servo.angle().unwrap(), 90.0);
+    assert_eq!(servo.pwm().period_ns().unwrap(), 20_000_000);
+    assert!(servo.pwm().enabled().unwrap());
+
+    servo.set_angle(-45.0).unwrap();
+    assert_eq!(servo.pulse_ns().unwrap(), 1_000_000);
+    servo.set_angle(270.0).unwrap();
+    assert_eq!(servo.pulse_ns().unwrap(), 2_000_000);
+    servo.set_pulse_ns(500_000).unwrap();
+    assert_eq!(servo.pulse_ns().unwrap(), 1_000_000);
+
+    assert!(matches!(servo.set_angle(f64::NAN), Err(Error::InvalidArgument(_))));
+
+    servo.disable().unwrap();
+    assert!(!servo.pwm().enabled().unwrap());
+}
+
+#[test]
+fn servo_rejects_invalid_configs() {
+    let root = FakeRoot::new("servo");
+    root.pwm_channel(1, DISABLED);
+    root.pwm_channel(2, DISABLED);
+
+    let config = ServoConfig { max_pulse_ns: 30_000_000, ..Default::default() };
+    assert!(matches!(Servo::new(Pwm::new(1, 0).unwrap(), config), Err(Error::InvalidArgument(_))));
+
+    let config = ServoConfig { min_angle: 90.0, max_angle: 90.0, ..Default::default() };
+    assert!(matches!(Servo::new(Pwm::new(2, 0).unwrap(), config), Err(Error::InvalidArgument(_))));
+}
+
+#[test]
+fn motor_sets_direction_pins_and_duty_cycle() {
+    let root = FakeRoot::new("servo");
+    root.pwm_channel(3, DISABLED);
+    let pwm = Pwm::new(3, 0).unwrap();
+    let (forward, forward_dir) = root.gpio_pin(30);
+    let (backward, backward_dir) = root.gpio_pin(31);
+    let motor = Motor::new(pwm, forward, backward, 1_000_000).unwrap();
+    assert_eq!(fs::read_to_string(forward_dir.join("direction")).unwrap(), "low");
+
+    motor.set_speed(0.5).unwrap();
+    assert_eq!(forward.value().unwrap(), Value::High);
+    assert_eq!(backward.value().unwrap(), Value::Low);
+    assert_eq!(motor.pwm().duty_cycle_ns().unwrap(), 500_000);
+    assert_eq!(motor.speed().unwrap(), 0.5);
+
+    motor.set_speed(-0.25).unwrap();
+    assert_eq!(fs::read_to_string(forward_dir.join("value")).unwrap(), "0");
+    assert_eq!(fs::read_to_string(backward_dir.join("value")).unwrap(), "1");
+    assert_eq!(motor.speed().unwrap(), -0.25);
+
+    motor.brake().unwrap();
+    assert_eq!(motor.speed().unwrap(), 0.0);
+    motor.coast().unwrap();
+    assert!(!motor.pwm().enabled().unwrap());
+
+    assert!(matches!(motor.set_speed(1.5), Err(Error::InvalidArgument(_))));
+}
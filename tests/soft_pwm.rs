@@ -0,0 +1,82 @@
+// Runs the software PWM against a fake sysfs GPIO tree under the temp dir.
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use periphery::soft_pwm::SoftPwm;
+use periphery::sys::pwm::PwmOutput;
+use periphery::Error;
+
+mod common;
+
+use common::FakeRoot;
+
+/// Wait for the thread to write `expected`
+fn wait_for_value(value: &PathBuf, expected: &str) {
+    let start = Instant::now();
+    while fs::read_to_string(value).unwrap().trim() != expected {
+        assert!(start.elapsed() < Duration::from_secs(5), "{} never became {}", value.display(), expected);
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn drives_the_pin_at_the_configured_duty_cycle() {
+    let root = FakeRoot::new("soft-pwm");
+    let (pin, dir) = root.gpio_pin(10);
+    let value = dir.join("value");
+    let pwm = SoftPwm::new(pin, 100.0).unwrap();
+    assert_eq!(pwm.period_ns().unwrap(), 10_000_000);
+    assert!(!pwm.enabled().unwrap());
+
+    pwm.set_duty_cycle(1.0).unwrap();
+    pwm.enable(true).unwrap();
+    wait_for_value(&value, "1");
+
+    pwm.set_duty_cycle(0.0).unwrap();
+    wait_for_value(&value, "0");
+
+    pwm.set_duty_cycle(0.5).unwrap();
+    wait_for_value(&value, "1");
+    wait_for_value(&value, "0");
+
+    pwm.enable(false).unwrap();
+    wait_for_value(&value, "0");
+}
+
+#[test]
+fn keeps_the_duty_ratio_across_frequency_changes() {
+    let root = FakeRoot::new("soft-pwm");
+    let (pin, _) = root.gpio_pin(11);
+    let pwm = SoftPwm::new(pin, 1000.0).unwrap();
+
+    pwm.set_duty_cycle(0.25).unwrap();
+    pwm.set_frequency_hz(50.0).unwrap();
+    assert_eq!(pwm.period_ns().unwrap(), 20_000_000);
+    assert_eq!(pwm.duty_cycle_ns().unwrap(), 5_000_000);
+
+    assert!(matches!(pwm.set_duty_cycle_ns(30_000_000), Err(Error::InvalidArgument(_))));
+    assert!(matches!(pwm.set_period_ns(1_000_000), Err(Error::InvalidArgument(_))));
+    assert!(matches!(pwm.set_frequency_hz(0.0), Err(Error::InvalidArgument(_))));
+}
+
+#[test]
+fn can_be_driven_through_the_pwm_output_trait() {
+    fn half<P: PwmOutput>(output: &P) -> periphery::Result<()> {
+        output.set_duty_cycle(0.5)?;
+        output.enable(true)
+    }
+
+    let root = FakeRoot::new("soft-pwm");
+    let (pin, dir) = root.gpio_pin(12);
+    let value = dir.join("value");
+    let pwm = SoftPwm::new(pin, 200.0).unwrap();
+    half(&pwm).unwrap();
+    assert_eq!(PwmOutput::duty_cycle(&pwm).unwrap(), 0.5);
+
+    wait_for_value(&value, "1");
+    drop(pwm);
+    assert_eq!(fs::read_to_string(value.with_file_name("direction")).unwrap(), "low");
+}
@@ -0,0 +1,71 @@
+// SPI types that can be exercised without a spidev device.
+
+use periphery::sys::spi::{BitOrder, Mode, Polarity, SPI, SpiModeFlags, SpiOptions, SpiTransaction, SpiWord, SpidevTransfer};
+use periphery::Error;
+
+#[test]
+fn mode_flags_combine_and_format() {
+    let mut flags = SpiModeFlags::from(Mode::Mode3) | SpiModeFlags::TX_QUAD;
+    assert_eq!(flags.bits(), 0x203);
+    assert_eq!(flags.mode(), Mode::Mode3);
+    assert!(flags.contains(SpiModeFlags::CPOL | SpiModeFlags::CPHA));
+
+    flags.set(SpiModeFlags::CPOL, false);
+    flags.insert(SpiModeFlags::THREE_WIRE);
+    assert_eq!(flags.mode(), Mode::Mode1);
+    assert_eq!(format!("{:?}", flags), "SpiModeFlags(CPHA | THREE_WIRE | TX_QUAD)");
+
+    flags.remove(SpiModeFlags::TX_QUAD);
+    assert!(!flags.contains(SpiModeFlags::TX_QUAD));
+    assert_eq!(flags & !SpiModeFlags::CPHA, SpiModeFlags::THREE_WIRE);
+
+    assert_eq!(format!("{:?}", SpiModeFlags::empty()), "SpiModeFlags(empty)");
+    assert_eq!(format!("{:?}", SpiModeFlags::from_bits(0x1001)), "SpiModeFlags(CPHA | 0x1000)");
+}
+
+#[test]
+fn word_types_cover_all_word_sizes() {
+    let ranges = [<u8 as SpiWord>::WORD_BITS, <u16 as SpiWord>::WORD_BITS, <u32 as SpiWord>::WORD_BITS];
+
+    for bits in 1..=32u8 {
+        assert_eq!(ranges.iter().filter(|range| range.contains(&bits)).count(), 1, "{} bits", bits);
+    }
+}
+
+#[test]
+fn options_combine_into_the_full_mode() {
+    let options = SpiOptions::new()
+        .mode(Mode::Mode2)
+        .bit_order(BitOrder::LsbFirst)
+        .ss_polarity(Polarity::ActiveHigh)
+        .mode_flags(SpiModeFlags::CPHA | SpiModeFlags::RX_DUAL);
+
+    assert_eq!(
+        options.flags(),
+        SpiModeFlags::CPOL | SpiModeFlags::CS_HIGH | SpiModeFlags::LSB_FIRST | SpiModeFlags::RX_DUAL
+    );
+    assert_eq!(SpiOptions::default().flags(), SpiModeFlags::empty());
+}
+
+#[test]
+fn open_reports_missing_devices_and_invalid_options() {
+    let missing = std::env::temp_dir().join(format!("periphery-spi-{}", std::process::id()));
+    assert!(matches!(SPI::open(&missing, &SpiOptions::new()), Err(Error::Io(_))));
+
+    // Options are validated before the device is touched
+    assert!(matches!(SPI::open(&missing, &SpiOptions::new().bits_per_word(0)), Err(Error::InvalidArgument(_))));
+}
+
+#[test]
+fn mismatched_transfer_buffers_are_rejected() {
+    let tx = [0u8; 4];
+    let mut rx = [0u8; 3];
+
+    match SpidevTransfer::read_write(&tx, &mut rx) {
+        Err(Error::InvalidArgument(msg)) => assert!(msg.contains("4 bytes to send and 3 to receive"), "{}", msg),
+        other => panic!("expected InvalidArgument, got {:?}", other),
+    }
+
+    let transaction = SpiTransaction::new().write(&tx).transfer(&tx, &mut rx);
+    assert_eq!(transaction.len(), 1);
+}